@@ -0,0 +1,65 @@
+//! Compares the spatial grid used by `River::distribute` against the brute
+//! force scan it replaced. Run with `cargo bench --bench cutoff`.
+
+use rivermap::glam::{Vec2, vec2};
+use rivermap::spatial::{SpatialGrid, farthest_within_scan};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const SPACING: f32 = 5.0;
+const RADIUS: f32 = 15.1;
+const MARGIN: usize = 6;
+
+/// A long, tightly wound river with plenty of near misses between bends.
+fn meander(len: usize) -> Vec<Vec2> {
+    let mut loc = Vec2::ZERO;
+    (0..len)
+        .map(|i| {
+            let t = i as f32 * 0.05;
+            let heading = 1.9 * t.sin() + 0.4 * (t * 0.17).cos();
+            loc += vec2(heading.cos(), heading.sin()) * SPACING;
+            loc
+        })
+        .collect()
+}
+
+fn time(mut f: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    f();
+    start.elapsed()
+}
+
+fn main() {
+    println!(
+        "{:>8} {:>12} {:>12} {:>8}",
+        "nodes", "scan", "grid", "speedup"
+    );
+    for len in [500, 1_000, 2_000, 5_000, 10_000] {
+        let locs = meander(len);
+
+        let mut scan_hits = Vec::with_capacity(len);
+        let scan = time(|| {
+            for (ind, &at) in locs.iter().enumerate() {
+                scan_hits.push(farthest_within_scan(&locs, at, RADIUS, ind + MARGIN..len));
+            }
+        });
+
+        let mut grid_hits = Vec::with_capacity(len);
+        let grid = time(|| {
+            let grid = SpatialGrid::new(RADIUS, locs.iter().copied());
+            for (ind, &at) in locs.iter().enumerate() {
+                grid_hits.push(grid.farthest_within(at, RADIUS, ind + MARGIN..len));
+            }
+        });
+
+        assert_eq!(
+            scan_hits, grid_hits,
+            "grid disagrees with scan at {len} nodes"
+        );
+        black_box((scan_hits, grid_hits));
+        println!(
+            "{len:>8} {scan:>12.2?} {grid:>12.2?} {:>7.1}x",
+            scan.as_secs_f64() / grid.as_secs_f64()
+        );
+    }
+}
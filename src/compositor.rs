@@ -1,20 +1,38 @@
 use nannou::{
     App, Frame,
     prelude::DeviceExt,
-    wgpu::{self, BindGroup, Buffer, BufferInitDescriptor, RenderPipeline},
+    wgpu::{self, BindGroup, Buffer, BufferInitDescriptor, RenderPipeline, Texture},
 };
 
-use crate::Render;
+use rivermap::style::{HistoryStyle, MapStyle, PaperStyle};
+use rivermap::{F_HEIGHT, F_WIDTH};
 
 #[derive(Debug)]
 pub struct Compositor {
     bind_group: BindGroup,
     render_pipeline: RenderPipeline,
     vertex_buffer: Buffer,
+    history_style: Buffer,
+    paper_style: Buffer,
+    view: Buffer,
+    history_extent: [f32; 2],
+}
+
+/// Where the map-sized history lands in the window and how old it is, laid
+/// out as the `View` struct in `compositor_fs.wgsl`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct ViewUniform {
+    /// Window size over map size, both in points.
+    history_extent: [f32; 2],
+    time: f32,
+    snapshot_every: f32,
 }
 
 impl Compositor {
-    pub fn new(app: &App, textures: &[&Render]) -> Self {
+    /// `textures` are the history, border and fill targets. The history
+    /// covers the map and the others the window.
+    pub fn new(app: &App, textures: &[&Texture], style: &MapStyle) -> Self {
         let vs_desc = wgpu::include_wgsl!("shaders/compositor_vs.wgsl");
         let fs_desc = wgpu::include_wgsl!("shaders/compositor_fs.wgsl");
         let window = app.main_window();
@@ -22,8 +40,35 @@ impl Compositor {
         let vs_mod = device.create_shader_module(vs_desc);
         let fs_mod = device.create_shader_module(fs_desc);
 
+        let history_style = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("history style"),
+            contents: HistoryUniform::new(&style.history).as_bytes(),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let paper_style = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("paper style"),
+            contents: PaperUniform::new(&style.paper).as_bytes(),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let (w, h) = window.inner_size_points();
+        let history_extent = [w / F_WIDTH, h / F_HEIGHT];
+        let view = ViewUniform {
+            history_extent,
+            time: 0.0,
+            snapshot_every: 1.0,
+        };
+        let view = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("view"),
+            contents: view.as_bytes(),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
         let bind_group_layout = create_bind_group_layout(device, textures);
-        let bind_group = create_bind_group(device, &bind_group_layout, textures);
+        let bind_group = create_bind_group(
+            device,
+            &bind_group_layout,
+            textures,
+            [&history_style, &paper_style, &view],
+        );
         let pipeline_layout = create_pipeline_layout(device, &bind_group_layout);
         let render_pipeline = create_render_pipeline(
             device,
@@ -45,9 +90,35 @@ impl Compositor {
             bind_group,
             render_pipeline,
             vertex_buffer,
+            history_style,
+            paper_style,
+            view,
+            history_extent,
         }
     }
 
+    /// Ages the history as of sim time `time`, counting in snapshots of
+    /// `snapshot_every` sim seconds.
+    pub fn set_time(&self, app: &App, time: f32, snapshot_every: f32) {
+        let view = ViewUniform {
+            history_extent: self.history_extent,
+            time,
+            snapshot_every,
+        };
+        let window = app.main_window();
+        window.queue().write_buffer(&self.view, 0, view.as_bytes());
+    }
+
+    /// Restyles the map from the next frame on.
+    pub fn set_style(&self, app: &App, style: &MapStyle) {
+        let window = app.main_window();
+        let queue = window.queue();
+        let history = HistoryUniform::new(&style.history);
+        queue.write_buffer(&self.history_style, 0, history.as_bytes());
+        let paper = PaperUniform::new(&style.paper);
+        queue.write_buffer(&self.paper_style, 0, paper.as_bytes());
+    }
+
     pub fn draw(&self, frame: &Frame) {
         let mut encoder = frame.command_encoder();
         let mut render_pass = wgpu::RenderPassBuilder::new()
@@ -66,36 +137,42 @@ impl Compositor {
     }
 }
 
-fn create_bind_group_layout(
-    device: &wgpu::Device,
-    texture_sample_type: &[&Render],
-) -> wgpu::BindGroupLayout {
+fn create_bind_group_layout(device: &wgpu::Device, textures: &[&Texture]) -> wgpu::BindGroupLayout {
     let mut layout_builder = wgpu::BindGroupLayoutBuilder::new();
-    for texture in texture_sample_type {
+    for texture in textures {
         layout_builder = layout_builder.texture(
             wgpu::ShaderStages::FRAGMENT,
-            true,
+            texture.sample_count() > 1,
             wgpu::TextureViewDimension::D2,
-            texture.texture.view().build().sample_type(),
+            texture.sample_type(),
         );
     }
-    layout_builder.build(device)
+    layout_builder
+        .uniform_buffer(wgpu::ShaderStages::FRAGMENT, false)
+        .uniform_buffer(wgpu::ShaderStages::FRAGMENT, false)
+        .uniform_buffer(wgpu::ShaderStages::FRAGMENT, false)
+        .build(device)
 }
 
 fn create_bind_group(
     device: &wgpu::Device,
     layout: &wgpu::BindGroupLayout,
-    textures: &[&Render],
+    textures: &[&Texture],
+    [history_style, paper_style, view]: [&wgpu::Buffer; 3],
 ) -> wgpu::BindGroup {
     let mut group_builder = wgpu::BindGroupBuilder::new();
     let built = textures
         .iter()
-        .map(|r| r.texture.view().build())
+        .map(|texture| texture.view().build())
         .collect::<Vec<_>>();
     for texture in &built {
         group_builder = group_builder.texture_view(texture);
     }
-    group_builder.build(device, layout)
+    group_builder
+        .buffer::<HistoryUniform>(history_style, 0..1)
+        .buffer::<PaperUniform>(paper_style, 0..1)
+        .buffer::<ViewUniform>(view, 0..1)
+        .build(device, layout)
 }
 
 fn create_pipeline_layout(
@@ -127,6 +204,95 @@ fn create_render_pipeline(
         .build(device)
 }
 
+/// [`HistoryStyle`] laid out as the `HistoryStyle` struct in `compositor_fs.wgsl`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct HistoryUniform {
+    palette: [[f32; 4]; HistoryStyle::MAX_PALETTE_STOPS],
+    patterns: [[u32; 4]; HistoryStyle::MAX_PATTERNS / 4],
+    num_stops: u32,
+    num_patterns: u32,
+    max_age: f32,
+    pattern_scale: f32,
+}
+
+impl HistoryUniform {
+    fn new(style: &HistoryStyle) -> Self {
+        let (stops, patterns) = (style.palette(), style.patterns());
+        let mut uniform = HistoryUniform {
+            palette: [[0.0; 4]; HistoryStyle::MAX_PALETTE_STOPS],
+            patterns: [[0; 4]; HistoryStyle::MAX_PATTERNS / 4],
+            num_stops: stops.len() as u32,
+            num_patterns: patterns.len() as u32,
+            max_age: style.max_age,
+            pattern_scale: style.pattern_scale,
+        };
+        for (slot, &[l, c, h]) in uniform.palette.iter_mut().zip(stops) {
+            *slot = [l, c, h, 0.0];
+        }
+        for (i, &pattern) in patterns.iter().enumerate() {
+            uniform.patterns[i / 4][i % 4] = pattern as u32;
+        }
+        uniform
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        unsafe { wgpu::bytes::from(self) }
+    }
+}
+
+impl ViewUniform {
+    fn as_bytes(&self) -> &[u8] {
+        unsafe { wgpu::bytes::from(self) }
+    }
+}
+
+/// [`PaperStyle`] laid out as the `PaperStyle` struct in `compositor_fs.wgsl`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct PaperUniform {
+    color: [f32; 4],
+    dark: [f32; 4],
+    ink: [f32; 4],
+    folds: [f32; 2],
+    blotch_scale: f32,
+    grain_scale: f32,
+    blotch_strength: f32,
+    grain_strength: f32,
+    fold_sharpness: f32,
+    vignette: f32,
+    stains: u32,
+    plain: u32,
+    _padding: [u32; 2],
+}
+
+impl PaperUniform {
+    fn new(style: &PaperStyle) -> Self {
+        let [l, c, h] = style.color;
+        let [dark_l, dark_c, dark_h] = style.dark;
+        let [ink_l, ink_c, ink_h] = style.ink;
+        PaperUniform {
+            color: [l, c, h, 0.0],
+            dark: [dark_l, dark_c, dark_h, 0.0],
+            ink: [ink_l, ink_c, ink_h, 0.0],
+            folds: style.folds,
+            blotch_scale: style.blotch_scale,
+            grain_scale: style.grain_scale,
+            blotch_strength: style.blotch_strength,
+            grain_strength: style.grain_strength,
+            fold_sharpness: style.fold_sharpness,
+            vignette: style.vignette,
+            stains: style.stains.min(PaperStyle::MAX_STAINS),
+            plain: style.plain as u32,
+            _padding: [0; 2],
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        unsafe { wgpu::bytes::from(self) }
+    }
+}
+
 // The vertex type that we will use to represent a point on our triangle.
 #[repr(C)]
 #[derive(Clone, Copy)]
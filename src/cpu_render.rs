@@ -0,0 +1,325 @@
+//! Headless replacement for the viewer's `Render` targets and `Compositor`.
+//!
+//! Layers are rasterized with the same multisample pattern as the window, and
+//! every sample is shaded with [`shading`](crate::shading) before resolving,
+//! just like `compositor_fs.wgsl` does.
+
+use crate::shading::{
+    NEVER_OCCUPIED, alpha_over, gamma_correct, history_color, oklch_to_lin, paper,
+};
+use crate::style::MapStyle;
+use crate::{F_HEIGHT, F_WIDTH, River};
+use glam::{Vec2, Vec3, const_vec2, vec2, vec4};
+use image::RgbaImage;
+use lyon::path::Path as LyonPath;
+use lyon::tessellation::{
+    BuffersBuilder, StrokeOptions, StrokeTessellator, StrokeVertex, VertexBuffers,
+};
+use std::{io, path::Path};
+
+/// Standard 4× MSAA sample offsets, in pixels from the pixel center.
+const SAMPLE_OFFSETS: [Vec2; 4] = [
+    const_vec2!([-2.0 / 16.0, -6.0 / 16.0]),
+    const_vec2!([6.0 / 16.0, -2.0 / 16.0]),
+    const_vec2!([-6.0 / 16.0, 2.0 / 16.0]),
+    const_vec2!([2.0 / 16.0, 6.0 / 16.0]),
+];
+
+/// Renders rivers to images without a GPU.
+///
+/// Like the viewer, it keeps the floodplain history between frames: call
+/// [`CpuRenderer::snapshot`] after every step a
+/// [`SnapshotClock`](crate::simulation::SnapshotClock) says is due and
+/// [`CpuRenderer::render`] to composite the current river over it.
+#[derive(Clone, Debug)]
+pub struct CpuRenderer {
+    width: u32,
+    height: u32,
+    /// World size covered by the image, centered on the origin.
+    world_size: Vec2,
+    /// Per sample, the sim time the river last covered it and whether that
+    /// was under a bank line, like the viewer's history target.
+    history: Vec<[f32; 2]>,
+    pub style: MapStyle,
+    /// Sim seconds between calls to [`CpuRenderer::snapshot`], which is the
+    /// unit [`HistoryStyle`](crate::style::HistoryStyle) ages are given in.
+    pub snapshot_every: f32,
+}
+
+impl CpuRenderer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self::with_world_size(width, height, vec2(F_WIDTH, F_HEIGHT))
+    }
+
+    pub fn with_world_size(width: u32, height: u32, world_size: Vec2) -> Self {
+        CpuRenderer {
+            width,
+            height,
+            world_size,
+            history: vec![[NEVER_OCCUPIED, 0.0]; (width * height) as usize * SAMPLE_OFFSETS.len()],
+            style: MapStyle::default(),
+            snapshot_every: 0.5,
+        }
+    }
+
+    /// Stamps the river's current extent into the history with its sim time.
+    pub fn snapshot(&mut self, river: &River) {
+        let time = river.time as f32;
+        let mut history = std::mem::take(&mut self.history);
+        self.fill_triangles(&river_triangles(river), |i| history[i] = [time, 0.0]);
+        for bank in river.banks() {
+            self.fill_triangles(&stroke_triangles(&bank, 1.0), |i| history[i] = [time, 1.0]);
+        }
+        self.history = history;
+    }
+
+    /// Composites paper, history, river fill and borders into an sRGB image.
+    pub fn render(&self, river: &River) -> RgbaImage {
+        let samples = self.history.len();
+        let mut fill = vec![false; samples];
+        let mut border = vec![false; samples];
+
+        for lake in &river.oxbows {
+            let mesh = lake.mesh();
+            let tris = mesh
+                .indices()
+                .iter()
+                .map(|&i| mesh.vertices()[i].0.truncate())
+                .collect::<Vec<_>>();
+            self.fill_triangles(&tris, |i| fill[i] = true);
+            let mut outline = lake.outline();
+            if let Some(&first) = outline.first() {
+                outline.push(first);
+            }
+            self.fill_triangles(&stroke_triangles(&outline, 1.0), |i| border[i] = true);
+        }
+        self.fill_triangles(&river_triangles(river), |i| fill[i] = true);
+        for bank in river.banks() {
+            self.fill_triangles(&stroke_triangles(&bank, 2.0), |i| border[i] = true);
+        }
+
+        let now = river.time as f32;
+        let ink = oklch_to_lin(Vec3::from(self.style.paper.ink));
+        let per_pixel = SAMPLE_OFFSETS.len();
+        RgbaImage::from_fn(self.width, self.height, |x, y| {
+            let tex_coords = vec2(
+                (x as f32 + 0.5) / self.width as f32,
+                (y as f32 + 0.5) / self.height as f32,
+            );
+            let paper = paper(tex_coords, &self.style.paper);
+            let first = (y * self.width + x) as usize * per_pixel;
+            let mut color = vec4(0.0, 0.0, 0.0, 0.0);
+            for s in first..first + per_pixel {
+                let [last_occupied, is_border] = self.history[s];
+                let age = (now - last_occupied).max(0.0) / self.snapshot_every;
+                let history = history_color(tex_coords, age, is_border, ink, &self.style.history);
+                let fill = paper * if fill[s] { 1.0 } else { 0.0 };
+                let border = if border[s] {
+                    ink.extend(1.0)
+                } else {
+                    vec4(0.0, 0.0, 0.0, 0.0)
+                };
+                color += alpha_over(border, alpha_over(fill, alpha_over(history, paper)));
+            }
+            color /= per_pixel as f32;
+            let encode = |c: f32| (gamma_correct(c.clamp(0.0, 1.0)) * 255.0).round() as u8;
+            image::Rgba([
+                encode(color.x),
+                encode(color.y),
+                encode(color.z),
+                (color.w.clamp(0.0, 1.0) * 255.0).round() as u8,
+            ])
+        })
+    }
+
+    pub fn save_png(&self, river: &River, path: impl AsRef<Path>) -> io::Result<()> {
+        self.render(river)
+            .save_with_format(path, image::ImageFormat::Png)
+            .map_err(io::Error::other)
+    }
+
+    /// Calls `cover` with the index of every sample inside any of the triangles.
+    fn fill_triangles(&self, tris: &[Vec2], mut cover: impl FnMut(usize)) {
+        let to_pixel = |p: Vec2| {
+            let uv = vec2(p.x / self.world_size.x + 0.5, 0.5 - p.y / self.world_size.y);
+            uv * vec2(self.width as f32, self.height as f32)
+        };
+        for tri in tris.chunks_exact(3) {
+            let [a, b, c] = [to_pixel(tri[0]), to_pixel(tri[1]), to_pixel(tri[2])];
+            if !(a.is_finite() && b.is_finite() && c.is_finite()) {
+                continue;
+            }
+            let area = (b - a).perp_dot(c - a);
+            if area == 0.0 {
+                continue;
+            }
+            let lo = a.min(b).min(c).floor().max(Vec2::ZERO);
+            let hi = a
+                .max(b)
+                .max(c)
+                .ceil()
+                .min(vec2(self.width as f32, self.height as f32));
+            for y in lo.y as u32..hi.y as u32 {
+                for x in lo.x as u32..hi.x as u32 {
+                    for (s, &offset) in SAMPLE_OFFSETS.iter().enumerate() {
+                        let p = vec2(x as f32 + 0.5, y as f32 + 0.5) + offset;
+                        let w0 = (b - a).perp_dot(p - a) / area;
+                        let w1 = (c - b).perp_dot(p - b) / area;
+                        let w2 = (a - c).perp_dot(p - c) / area;
+                        if w0 >= 0.0 && w1 >= 0.0 && w2 >= 0.0 {
+                            cover((y * self.width + x) as usize * SAMPLE_OFFSETS.len() + s);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The river's fill mesh as a flat triangle list.
+pub fn river_triangles(river: &River) -> Vec<Vec2> {
+    let mesh = &river.river_builder;
+    mesh.indices()
+        .iter()
+        .map(|&i| mesh.vertices()[i].0.truncate())
+        .collect()
+}
+
+/// A polyline of the given weight as a flat triangle list, like nannou's `polyline`.
+pub fn stroke_triangles(points: &[Vec2], weight: f32) -> Vec<Vec2> {
+    let mut builder = LyonPath::builder();
+    let mut points = points.iter();
+    let Some(first) = points.next() else {
+        return Vec::new();
+    };
+    builder.begin(lyon::math::point(first.x, first.y));
+    for p in points {
+        builder.line_to(lyon::math::point(p.x, p.y));
+    }
+    builder.end(false);
+    let path = builder.build();
+
+    let mut buffers: VertexBuffers<Vec2, u32> = VertexBuffers::new();
+    StrokeTessellator::new()
+        .tessellate_path(
+            &path,
+            &StrokeOptions::default().with_line_width(weight),
+            &mut BuffersBuilder::new(&mut buffers, |v: StrokeVertex| {
+                vec2(v.position().x, v.position().y)
+            }),
+        )
+        .unwrap();
+    buffers
+        .indices
+        .iter()
+        .map(|&i| buffers.vertices[i as usize])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Node;
+    use crate::style::{HatchPattern, PaperStyle};
+    use crate::terrain::TiltedPlane;
+    use glam::Vec4;
+
+    const SIZE: u32 = 100;
+
+    /// One world unit per pixel, on plain paper with colored ink.
+    fn renderer() -> CpuRenderer {
+        let mut renderer = CpuRenderer::with_world_size(SIZE, SIZE, vec2(100.0, 100.0));
+        renderer.style.paper = PaperStyle {
+            ink: [0.5, 0.1, 30.0],
+            ..PaperStyle::modern()
+        };
+        renderer.style.history.patterns = vec![HatchPattern::Solid];
+        renderer
+    }
+
+    /// A straight river across the whole image at height `y`, as wide as
+    /// [`SimParams::width_base`](crate::SimParams::width_base).
+    fn straight_river(y: f32) -> River {
+        let node = |x| Node {
+            loc: vec2(x, y),
+            ..Default::default()
+        };
+        let mut river = River {
+            start: node(-60.0),
+            segments: (-11..=11).map(|i| node(i as f32 * 5.0)).collect(),
+            end: node(60.0),
+            ..River::default()
+        };
+        river.recompute();
+        river.tesselate(&TiltedPlane::default());
+        river
+    }
+
+    /// Pixel `(x, y)` and the texture coordinates it is shaded at.
+    fn pixel_at(world: Vec2) -> (u32, u32, Vec2) {
+        let (x, y) = ((world.x + 50.0) as u32, (50.0 - world.y) as u32);
+        let tex_coords = vec2(x as f32 + 0.5, y as f32 + 0.5) / SIZE as f32;
+        (x, y, tex_coords)
+    }
+
+    fn assert_color(image: &RgbaImage, x: u32, y: u32, expected: Vec4) {
+        let encode = |c: f32| (gamma_correct(c.clamp(0.0, 1.0)) * 255.0).round() as i32;
+        let expected = [
+            encode(expected.x),
+            encode(expected.y),
+            encode(expected.z),
+            (expected.w * 255.0).round() as i32,
+        ];
+        let actual = image.get_pixel(x, y).0.map(i32::from);
+        // Resolving four equal samples can round the last bit differently.
+        let close = (actual.iter().zip(&expected)).all(|(a, e)| (a - e).abs() <= 1);
+        assert!(
+            close,
+            "pixel ({x}, {y}) is {actual:?}, expected {expected:?}"
+        );
+    }
+
+    #[test]
+    fn empty_map_is_paper() {
+        let renderer = renderer();
+        let image = renderer.render(&River::default());
+        let paper = paper(Vec2::ZERO, &renderer.style.paper);
+        for y in 0..SIZE {
+            for x in 0..SIZE {
+                assert_color(&image, x, y, paper);
+            }
+        }
+    }
+
+    #[test]
+    fn fill_is_paper_and_banks_are_ink() {
+        let renderer = renderer();
+        let river = straight_river(0.0);
+        let image = renderer.render(&river);
+
+        let (x, y, tex_coords) = pixel_at(vec2(0.0, 0.0));
+        assert_color(&image, x, y, paper(tex_coords, &renderer.style.paper));
+
+        let bank = river.params.width_base / 2.0;
+        let (x, y, _) = pixel_at(vec2(0.0, bank));
+        let ink = oklch_to_lin(Vec3::from(renderer.style.paper.ink));
+        assert_color(&image, x, y, ink.extend(1.0));
+    }
+
+    #[test]
+    fn snapshots_age_into_history() {
+        let mut renderer = renderer();
+        renderer.snapshot(&straight_river(20.0));
+        let mut river = straight_river(-20.0);
+        river.time = 4.0 * renderer.snapshot_every as f64;
+        let image = renderer.render(&river);
+
+        let (x, y, tex_coords) = pixel_at(vec2(0.0, 20.0));
+        let style = &renderer.style;
+        let ink = oklch_to_lin(Vec3::from(style.paper.ink));
+        let history = history_color(tex_coords, 4.0, 0.0, ink, &style.history);
+        assert_eq!(history.w, 1.0);
+        let expected = alpha_over(history, paper(tex_coords, &style.paper));
+        assert_color(&image, x, y, expected);
+    }
+}
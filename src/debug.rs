@@ -0,0 +1,110 @@
+use nannou::prelude::*;
+use rivermap::simulation::Simulation;
+use rivermap::{F_HEIGHT_H, F_WIDTH_H};
+
+/// Side of a terrain heatmap cell, in world units.
+static TERRAIN_CELL: f32 = 10.0;
+static FRAME_ARROW_LENGTH: f32 = 10.0;
+/// World units of arrow per unit of downhill pull per second.
+static GRADIENT_ARROW_SCALE: f32 = 10.0;
+
+/// Which diagnostics to draw over the map.
+#[derive(Copy, Clone, Debug)]
+pub struct DebugLayers {
+    /// Toggled with `G`. Nothing is drawn while this is off.
+    pub enabled: bool,
+    /// Tangent (blue) and bitangent (red) of every node.
+    pub frames: bool,
+    /// The pull of the `Downhill` force on every node.
+    pub gradients: bool,
+    /// Nodes `River::distribute` would cut the loop between.
+    pub cutoffs: bool,
+    /// The river width sampled from the widthmap, across every node.
+    pub widths: bool,
+    /// The heightmap, blue where low and red where high.
+    pub terrain: bool,
+}
+
+impl Default for DebugLayers {
+    fn default() -> Self {
+        DebugLayers {
+            enabled: false,
+            frames: true,
+            gradients: true,
+            cutoffs: true,
+            widths: false,
+            terrain: false,
+        }
+    }
+}
+
+pub fn draw_overlay(draw: &Draw, sim: &Simulation, layers: &DebugLayers) {
+    if !layers.enabled {
+        return;
+    }
+    if layers.terrain {
+        draw_terrain(draw, sim);
+    }
+
+    // Nodes placed by the last `distribute` have no frame until the next step.
+    let mut river = sim.river.clone();
+    river.recompute();
+    let params = river.params;
+    for node in river.nodes() {
+        let loc = node.loc;
+        if layers.widths {
+            let half_width = node.tangent.perp() * params.width_at(&*sim.widthmap, loc) / 2.0;
+            draw.line()
+                .start(loc - half_width)
+                .end(loc + half_width)
+                .weight(1.0)
+                .color(GREEN);
+        }
+        if layers.frames {
+            draw.arrow()
+                .start(loc)
+                .end(loc + node.tangent * FRAME_ARROW_LENGTH)
+                .weight(1.0)
+                .color(BLUE);
+            draw.arrow()
+                .start(loc)
+                .end(loc + node.bitangent * FRAME_ARROW_LENGTH)
+                .weight(1.0)
+                .color(RED);
+        }
+        if layers.gradients {
+            let pull = -sim.heightmap.gradient(loc) * params.gradient_weight;
+            draw.arrow()
+                .start(loc)
+                .end(loc + pull * GRADIENT_ARROW_SCALE)
+                .weight(1.0)
+                .color(DARKORANGE);
+        }
+    }
+
+    if layers.cutoffs {
+        for (a, b) in river.cutoff_candidates() {
+            draw.line().start(a).end(b).weight(2.0).color(MAGENTA);
+            draw.ellipse().xy(a).radius(2.0).color(MAGENTA);
+            draw.ellipse().xy(b).radius(2.0).color(MAGENTA);
+        }
+    }
+}
+
+fn draw_terrain(draw: &Draw, sim: &Simulation) {
+    let columns = (2.0 * F_WIDTH_H / TERRAIN_CELL).ceil() as usize;
+    let rows = (2.0 * F_HEIGHT_H / TERRAIN_CELL).ceil() as usize;
+    for row in 0..rows {
+        for column in 0..columns {
+            let center = vec2(
+                -F_WIDTH_H + (column as f32 + 0.5) * TERRAIN_CELL,
+                -F_HEIGHT_H + (row as f32 + 0.5) * TERRAIN_CELL,
+            );
+            let t = (sim.heightmap.height(center) * 0.5 + 0.5).clamp(0.0, 1.0);
+            draw.rect()
+                .xy(center)
+                .w_h(TERRAIN_CELL, TERRAIN_CELL)
+                .rgba(t, 0.2, 1.0 - t, 0.5);
+        }
+    }
+}
@@ -0,0 +1,284 @@
+use crate::River;
+use crate::cpu_render::river_triangles;
+use glam::{Vec2, vec2};
+use image::{ImageBuffer, Luma};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Where and when the river has flowed, accumulated on a grid over the map.
+///
+/// Call [`FloodplainGrid::snapshot`] whenever floodplain history is recorded,
+/// as with [`CpuRenderer::snapshot`](crate::cpu_render::CpuRenderer::snapshot).
+/// A cell counts as visited when its center is inside the river's fill mesh.
+///
+/// Cells are stored row by row from the top of the map, like image pixels.
+#[derive(Clone, Debug)]
+pub struct FloodplainGrid {
+    columns: usize,
+    rows: usize,
+    cell_size: f32,
+    /// World position of the top left corner of the grid.
+    origin: Vec2,
+    /// Sim time of the first snapshot that covered each cell, NaN if none did.
+    first_visit: Vec<f32>,
+    /// Sim time of the latest snapshot that covered each cell, NaN if none did.
+    last_visit: Vec<f32>,
+    /// How many snapshots covered each cell.
+    visits: Vec<u32>,
+    /// Snapshots taken so far.
+    snapshots: u32,
+    /// The number of the latest snapshot that covered each cell, counting
+    /// from 1, so overlapping triangles only count once.
+    marks: Vec<u32>,
+}
+
+/// One of the per-cell arrays of a [`FloodplainGrid`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FloodplainLayer {
+    FirstVisit,
+    LastVisit,
+    Visits,
+}
+
+impl FloodplainLayer {
+    pub const ALL: [FloodplainLayer; 3] = [
+        FloodplainLayer::FirstVisit,
+        FloodplainLayer::LastVisit,
+        FloodplainLayer::Visits,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FloodplainLayer::FirstVisit => "first-visit",
+            FloodplainLayer::LastVisit => "last-visit",
+            FloodplainLayer::Visits => "visits",
+        }
+    }
+}
+
+impl FloodplainGrid {
+    /// A grid of `cell_size` world units covering `size` around the origin,
+    /// which the river has never visited.
+    pub fn new(size: Vec2, cell_size: f32) -> Self {
+        let columns = (size.x / cell_size).ceil().max(1.0) as usize;
+        let rows = (size.y / cell_size).ceil().max(1.0) as usize;
+        let cells = columns * rows;
+        FloodplainGrid {
+            columns,
+            rows,
+            cell_size,
+            origin: vec2(-size.x, size.y) / 2.0,
+            first_visit: vec![f32::NAN; cells],
+            last_visit: vec![f32::NAN; cells],
+            visits: vec![0; cells],
+            snapshots: 0,
+            marks: vec![0; cells],
+        }
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    pub fn first_visit(&self) -> &[f32] {
+        &self.first_visit
+    }
+
+    pub fn last_visit(&self) -> &[f32] {
+        &self.last_visit
+    }
+
+    pub fn visits(&self) -> &[u32] {
+        &self.visits
+    }
+
+    /// Records every cell under the river at its current sim time. Cells are
+    /// counted once per snapshot, however many triangles cover them, and
+    /// again by every later snapshot even at the same sim time.
+    pub fn snapshot(&mut self, river: &River) {
+        self.snapshot_triangles(&river_triangles(river), river.time as f32);
+    }
+
+    fn snapshot_triangles(&mut self, tris: &[Vec2], time: f32) {
+        self.snapshots += 1;
+        let mark = self.snapshots;
+        for tri in tris.chunks_exact(3) {
+            self.cover([tri[0], tri[1], tri[2]], |grid, i| {
+                if grid.marks[i] == mark {
+                    return;
+                }
+                grid.marks[i] = mark;
+                if grid.visits[i] == 0 {
+                    grid.first_visit[i] = time;
+                }
+                grid.last_visit[i] = time;
+                grid.visits[i] += 1;
+            });
+        }
+    }
+
+    /// Calls `visit` with the index of every cell whose center is inside `tri`.
+    fn cover(&mut self, tri: [Vec2; 3], mut visit: impl FnMut(&mut Self, usize)) {
+        let [a, b, c] =
+            tri.map(|p| vec2(p.x - self.origin.x, self.origin.y - p.y) / self.cell_size);
+        if !(a.is_finite() && b.is_finite() && c.is_finite()) {
+            return;
+        }
+        let area = (b - a).perp_dot(c - a);
+        if area == 0.0 {
+            return;
+        }
+        let size = vec2(self.columns as f32, self.rows as f32);
+        let lo = a.min(b).min(c).floor().max(Vec2::ZERO);
+        let hi = a.max(b).max(c).ceil().min(size);
+        for y in lo.y as usize..hi.y as usize {
+            for x in lo.x as usize..hi.x as usize {
+                let p = vec2(x as f32 + 0.5, y as f32 + 0.5);
+                let w0 = (b - a).perp_dot(p - a) / area;
+                let w1 = (c - b).perp_dot(p - b) / area;
+                let w2 = (a - c).perp_dot(p - c) / area;
+                if w0 >= 0.0 && w1 >= 0.0 && w2 >= 0.0 {
+                    visit(self, y * self.columns + x);
+                }
+            }
+        }
+    }
+
+    /// `layer` as a 16-bit grayscale image, one pixel per cell.
+    ///
+    /// Times are scaled so the latest visit is white, with black left for
+    /// cells that were never visited. Visit counts are stored as is, up to
+    /// 65535.
+    pub fn image(&self, layer: FloodplainLayer) -> ImageBuffer<Luma<u16>, Vec<u16>> {
+        let latest = self
+            .last_visit
+            .iter()
+            .copied()
+            .filter(|t| !t.is_nan())
+            .fold(0.0f32, f32::max);
+        let time = |t: f32| {
+            if t.is_nan() {
+                0
+            } else if latest > 0.0 {
+                1 + (t / latest * (u16::MAX - 1) as f32).round() as u16
+            } else {
+                u16::MAX
+            }
+        };
+        let pixels = match layer {
+            FloodplainLayer::FirstVisit => self.first_visit.iter().map(|&t| time(t)).collect(),
+            FloodplainLayer::LastVisit => self.last_visit.iter().map(|&t| time(t)).collect(),
+            FloodplainLayer::Visits => (self.visits.iter())
+                .map(|&n| n.min(u16::MAX as u32) as u16)
+                .collect(),
+        };
+        ImageBuffer::from_raw(self.columns as u32, self.rows as u32, pixels)
+            .expect("one pixel per cell")
+    }
+
+    pub fn save_png(&self, layer: FloodplainLayer, path: impl AsRef<Path>) -> io::Result<()> {
+        self.image(layer)
+            .save_with_format(path, image::ImageFormat::Png)
+            .map_err(io::Error::other)
+    }
+
+    /// Writes `layer` as bare little-endian values, one per cell with no
+    /// header: `f32` sim times, NaN where never visited, or `u32` counts.
+    pub fn save_raw(&self, layer: FloodplainLayer, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        match layer {
+            FloodplainLayer::FirstVisit => write_all(&mut out, &self.first_visit, f32::to_le_bytes),
+            FloodplainLayer::LastVisit => write_all(&mut out, &self.last_visit, f32::to_le_bytes),
+            FloodplainLayer::Visits => write_all(&mut out, &self.visits, u32::to_le_bytes),
+        }?;
+        out.flush()
+    }
+}
+
+fn write_all<T: Copy>(
+    out: &mut impl Write,
+    values: &[T],
+    to_bytes: impl Fn(T) -> [u8; 4],
+) -> io::Result<()> {
+    for &value in values {
+        out.write_all(&to_bytes(value))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One cell per world unit over a 100 by 100 map.
+    fn grid() -> FloodplainGrid {
+        FloodplainGrid::new(vec2(100.0, 100.0), 1.0)
+    }
+
+    /// Index of the cell whose center is nearest `world`.
+    fn cell(grid: &FloodplainGrid, world: Vec2) -> usize {
+        let (x, y) = ((world.x + 50.0) as usize, (50.0 - world.y) as usize);
+        y * grid.columns() + x
+    }
+
+    /// Two triangles covering the rectangle from `lo` to `hi`.
+    fn rect(lo: Vec2, hi: Vec2) -> Vec<Vec2> {
+        let (a, b, c, d) = (lo, vec2(hi.x, lo.y), hi, vec2(lo.x, hi.y));
+        vec![a, b, c, a, c, d]
+    }
+
+    #[test]
+    fn moved_river_keeps_first_and_last_visits() {
+        let mut grid = grid();
+        grid.snapshot_triangles(&rect(vec2(-40.0, -10.0), vec2(40.0, 10.0)), 1.0);
+        grid.snapshot_triangles(&rect(vec2(-40.0, 0.0), vec2(40.0, 20.0)), 2.0);
+
+        let both = cell(&grid, vec2(0.5, 5.5));
+        assert_eq!(grid.visits()[both], 2);
+        assert_eq!(grid.first_visit()[both], 1.0);
+        assert_eq!(grid.last_visit()[both], 2.0);
+
+        let left_behind = cell(&grid, vec2(0.5, -5.5));
+        assert_eq!(grid.visits()[left_behind], 1);
+        assert_eq!(grid.first_visit()[left_behind], 1.0);
+        assert_eq!(grid.last_visit()[left_behind], 1.0);
+
+        let moved_into = cell(&grid, vec2(0.5, 15.5));
+        assert_eq!(grid.visits()[moved_into], 1);
+        assert_eq!(grid.first_visit()[moved_into], 2.0);
+        assert_eq!(grid.last_visit()[moved_into], 2.0);
+
+        let dry = cell(&grid, vec2(0.5, 30.5));
+        assert_eq!(grid.visits()[dry], 0);
+        assert!(grid.first_visit()[dry].is_nan());
+        assert!(grid.last_visit()[dry].is_nan());
+    }
+
+    #[test]
+    fn overlapping_triangles_count_once() {
+        let mut grid = grid();
+        let mut tris = rect(vec2(-20.0, -20.0), vec2(20.0, 20.0));
+        tris.extend(rect(vec2(-10.0, -10.0), vec2(30.0, 30.0)));
+        grid.snapshot_triangles(&tris, 1.0);
+        assert_eq!(grid.visits().iter().max(), Some(&1));
+        assert_eq!(grid.visits()[cell(&grid, vec2(0.5, 0.5))], 1);
+    }
+
+    #[test]
+    fn snapshots_at_the_same_time_each_count() {
+        let mut grid = grid();
+        let tris = rect(vec2(-10.0, -10.0), vec2(10.0, 10.0));
+        grid.snapshot_triangles(&tris, 1.0);
+        grid.snapshot_triangles(&tris, 1.0);
+        assert_eq!(grid.visits()[cell(&grid, vec2(0.5, 0.5))], 2);
+    }
+}
@@ -0,0 +1,95 @@
+use crate::{Node, SimParams, Terrain};
+use glam::Vec2;
+use std::fmt::Debug;
+
+/// Everything outside the river that a [`Force`] may react to.
+#[derive(Copy, Clone, Debug)]
+pub struct Environment<'a> {
+    pub heightmap: &'a dyn Terrain,
+    /// Sets the channel width through [`SimParams::width_at`].
+    pub widthmap: &'a dyn Terrain,
+    pub params: &'a SimParams,
+    /// Sim time at the start of the step.
+    pub time: f64,
+    /// Effective time step, already adjusted by
+    /// [`SimParams::slowdown`] and [`SimParams::speed`].
+    pub dt: f32,
+}
+
+/// Something that moves river nodes.
+///
+/// Every step, [`River::step`](crate::River::step) asks each of its forces
+/// how far to move every free node and applies the sum. Forces see the
+/// river as it was at the start of the step, so their order does not matter.
+pub trait Force: ForceClone + Debug + Send + Sync {
+    /// Displacement of `node` over one step, given its upstream and downstream neighbors.
+    fn displacement(&self, prev: &Node, node: &Node, next: &Node, env: &Environment) -> Vec2;
+}
+
+/// Lets `Box<dyn Force>` be cloned along with its river.
+pub trait ForceClone {
+    fn clone_box(&self) -> Box<dyn Force>;
+}
+
+impl<T: Force + Clone + 'static> ForceClone for T {
+    fn clone_box(&self) -> Box<dyn Force> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Force> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// Pushes nodes downstream along the river, scaled by [`SimParams::tangent_weight`].
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TangentPush;
+
+impl Force for TangentPush {
+    fn displacement(&self, _: &Node, node: &Node, _: &Node, env: &Environment) -> Vec2 {
+        node.tangent * env.params.tangent_weight * env.dt
+    }
+}
+
+/// Pulls nodes away from the inside of their bend, scaled by
+/// [`SimParams::bitangent_weight`].
+#[derive(Copy, Clone, Debug, Default)]
+pub struct BitangentPull;
+
+impl Force for BitangentPull {
+    fn displacement(&self, _: &Node, node: &Node, _: &Node, env: &Environment) -> Vec2 {
+        -node.bitangent * env.params.bitangent_weight * env.dt
+    }
+}
+
+/// Rolls nodes down the heightmap, scaled by [`SimParams::gradient_weight`].
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Downhill;
+
+impl Force for Downhill {
+    fn displacement(&self, _: &Node, node: &Node, _: &Node, env: &Environment) -> Vec2 {
+        -env.heightmap.gradient(node.loc) * env.params.gradient_weight * env.dt
+    }
+}
+
+/// Howard–Knutson style bank migration.
+///
+/// Each node moves along its normal at a rate proportional to
+/// `local_curvature_weight * C + upstream_curvature_weight * C_up`, where
+/// `C_up` is [`Node::upstream_curvature`], scaled by the channel width at
+/// the node. Since the upstream term outweighs
+/// the local one, bends grow outward and migrate downstream.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct BankMigration;
+
+impl Force for BankMigration {
+    fn displacement(&self, _: &Node, node: &Node, _: &Node, env: &Environment) -> Vec2 {
+        let params = env.params;
+        let bank_velocity = params.local_curvature_weight * node.curvature
+            + params.upstream_curvature_weight * node.upstream_curvature;
+        let width = params.width_at(env.widthmap, node.loc);
+        -node.tangent.perp() * bank_velocity * width * params.migration_rate * env.dt
+    }
+}
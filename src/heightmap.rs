@@ -0,0 +1,35 @@
+use crate::params::HeightmapParams;
+use crate::terrain::Terrain;
+use crate::{F_HEIGHT_H, F_WIDTH_H};
+use glam::Vec2;
+use noise::{Fbm, MultiFractal, NoiseFn, Seedable};
+
+/// Fractal noise terrain that rises to a wall of 1.0 outside the map.
+#[derive(Clone, Debug)]
+pub struct Heightmap {
+    perlin: Fbm,
+    scale: f64,
+}
+
+impl Heightmap {
+    pub fn new(seed: u32, params: &HeightmapParams) -> Self {
+        Heightmap {
+            perlin: Fbm::new().set_octaves(params.octaves).set_seed(seed),
+            scale: params.scale as f64,
+        }
+    }
+
+    fn in_bounds(xy: Vec2) -> bool {
+        xy.x < F_WIDTH_H && xy.x > -F_WIDTH_H && xy.y < F_HEIGHT_H && xy.y > -F_HEIGHT_H
+    }
+}
+
+impl Terrain for Heightmap {
+    fn height(&self, xy: Vec2) -> f32 {
+        if Heightmap::in_bounds(xy) {
+            self.perlin.get((xy.as_f64() / self.scale).to_array()) as f32
+        } else {
+            1.0
+        }
+    }
+}
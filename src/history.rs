@@ -0,0 +1,198 @@
+use nannou::{
+    App,
+    prelude::{DeviceExt, Vec2},
+    wgpu::{self, BufferInitDescriptor, RenderPipeline, Texture, TextureBuilder},
+};
+
+use rivermap::cpu_render::{river_triangles, stroke_triangles};
+use rivermap::shading::NEVER_OCCUPIED;
+use rivermap::{F_HEIGHT, F_HEIGHT_H, F_WIDTH, F_WIDTH_H, River};
+
+/// The floodplain history, covering the map rather than the window.
+///
+/// Every pixel holds the sim time the river last covered it and whether that
+/// was under a bank line. Stamps replace what was there instead of blending
+/// with it, so ages stay exact however long the river runs.
+#[derive(Debug)]
+pub struct History {
+    pub texture: Texture,
+    render_pipeline: RenderPipeline,
+}
+
+impl History {
+    /// 32-bit floats can't be blended, which is fine as stamps never are.
+    const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg32Float;
+
+    /// A history the river has never been in, at the window's current DPI.
+    pub fn new(app: &App) -> Self {
+        let window = app.main_window();
+        let device = window.device();
+        let texture = Self::target(device, Self::size_at(window.scale_factor()));
+
+        let vs_mod = device.create_shader_module(wgpu::include_wgsl!("shaders/history_vs.wgsl"));
+        let fs_mod = device.create_shader_module(wgpu::include_wgsl!("shaders/history_fs.wgsl"));
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[],
+            push_constant_ranges: &[],
+        });
+        let render_pipeline = wgpu::RenderPipelineBuilder::from_layout(&pipeline_layout, &vs_mod)
+            .fragment_shader(&fs_mod)
+            .color_state(wgpu::ColorTargetState {
+                format: Self::FORMAT,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })
+            .add_vertex_buffer::<Vertex>(&Vertex::ATTRIBUTES)
+            .primitive_topology(wgpu::PrimitiveTopology::TriangleList)
+            .build(device);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("clear history"),
+        });
+        let never = wgpu::Color {
+            r: NEVER_OCCUPIED as f64,
+            ..wgpu::Color::TRANSPARENT
+        };
+        wgpu::RenderPassBuilder::new()
+            .color_attachment(&texture.view().build(), |color| {
+                color.load_op(wgpu::LoadOp::Clear(never))
+            })
+            .begin(&mut encoder);
+        window.queue().submit([encoder.finish()]);
+
+        History {
+            texture,
+            render_pipeline,
+        }
+    }
+
+    /// Moves the history to a new texture if the window's DPI has changed
+    /// since it was made, resampling what has been stamped so far. Returns
+    /// whether it did.
+    pub fn rescale(&mut self, app: &App) -> bool {
+        let window = app.main_window();
+        let device = window.device();
+        let size = Self::size_at(window.scale_factor());
+        if self.texture.size() == size {
+            return false;
+        }
+        let texture = Self::target(device, size);
+
+        let vs_mod =
+            device.create_shader_module(wgpu::include_wgsl!("shaders/history_resample_vs.wgsl"));
+        let fs_mod =
+            device.create_shader_module(wgpu::include_wgsl!("shaders/history_resample_fs.wgsl"));
+        let bind_group_layout = wgpu::BindGroupLayoutBuilder::new()
+            .texture(
+                wgpu::ShaderStages::FRAGMENT,
+                false,
+                wgpu::TextureViewDimension::D2,
+                self.texture.sample_type(),
+            )
+            .build(device);
+        let old_view = self.texture.view().build();
+        let bind_group = wgpu::BindGroupBuilder::new()
+            .texture_view(&old_view)
+            .build(device, &bind_group_layout);
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let render_pipeline = wgpu::RenderPipelineBuilder::from_layout(&pipeline_layout, &vs_mod)
+            .fragment_shader(&fs_mod)
+            .color_state(wgpu::ColorTargetState {
+                format: Self::FORMAT,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })
+            .primitive_topology(wgpu::PrimitiveTopology::TriangleList)
+            .build(device);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("rescale history"),
+        });
+        let view = texture.view().build();
+        let mut render_pass = wgpu::RenderPassBuilder::new()
+            .color_attachment(&view, |color| color.load_op(wgpu::LoadOp::Load))
+            .begin(&mut encoder);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.set_pipeline(&render_pipeline);
+        render_pass.draw(0..3, 0..1);
+        drop(render_pass);
+        window.queue().submit([encoder.finish()]);
+
+        self.texture = texture;
+        true
+    }
+
+    /// Pixel size of the map at `scale_factor`.
+    fn size_at(scale_factor: f32) -> [u32; 2] {
+        [F_WIDTH, F_HEIGHT].map(|s| (s * scale_factor).ceil().max(1.0) as u32)
+    }
+
+    fn target(device: &wgpu::Device, size: [u32; 2]) -> Texture {
+        TextureBuilder::new()
+            .size(size)
+            .usage(wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING)
+            .format(Self::FORMAT)
+            .build(device)
+    }
+
+    /// Stamps where the river is now with the sim time `time`, banks last.
+    pub fn stamp(&self, app: &App, river: &River, time: f32) {
+        let mut vertices = stamped(&river_triangles(river), [time, 0.0]);
+        for bank in river.banks() {
+            vertices.extend(stamped(&stroke_triangles(&bank, 1.0), [time, 1.0]));
+        }
+        if vertices.is_empty() {
+            return;
+        }
+        let window = app.main_window();
+        let device = window.device();
+        let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("history stamp"),
+            contents: unsafe { wgpu::bytes::from_slice(&vertices) },
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        // Submitted on its own rather than with a frame, as several steps
+        // may each stamp between two frames.
+        let view = self.texture.view().build();
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("stamp history"),
+        });
+        let mut render_pass = wgpu::RenderPassBuilder::new()
+            .color_attachment(&view, |color| color.load_op(wgpu::LoadOp::Load))
+            .begin(&mut encoder);
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.draw(0..vertices.len() as u32, 0..1);
+        drop(render_pass);
+        window.queue().submit([encoder.finish()]);
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Vertex {
+    /// Clip space, so the map exactly covers the target.
+    position: [f32; 2],
+    stamp: [f32; 2],
+}
+
+impl Vertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2];
+}
+
+/// `tris` in world units as vertices carrying `stamp`.
+fn stamped(tris: &[Vec2], stamp: [f32; 2]) -> Vec<Vertex> {
+    tris.iter()
+        .map(|p| Vertex {
+            position: [p.x / F_WIDTH_H, p.y / F_HEIGHT_H],
+            stamp,
+        })
+        .collect()
+}
@@ -0,0 +1,39 @@
+//! Headless meandering river simulation.
+//!
+//! Everything in here works on plain `glam` types and an explicit time step,
+//! so it can run without a window. The nannou viewer lives in the `rivermap`
+//! binary behind the `gui` feature.
+
+pub mod cpu_render;
+pub mod floodplain;
+pub mod force;
+pub mod heightmap;
+pub mod oxbow;
+pub mod params;
+pub mod preset;
+pub mod river;
+pub mod save;
+pub mod shading;
+pub mod simulation;
+pub mod spatial;
+pub mod style;
+pub mod svg;
+pub mod terrain;
+
+pub use glam;
+
+pub use crate::floodplain::FloodplainGrid;
+pub use crate::heightmap::Heightmap;
+pub use crate::oxbow::OxbowLake;
+pub use crate::params::SimParams;
+pub use crate::preset::{Preset, PresetFile, apply_preset};
+pub use crate::river::{Node, River, RiverMeshBuilder};
+pub use crate::simulation::Simulation;
+pub use crate::terrain::Terrain;
+
+pub static WIDTH: u32 = 720;
+pub static HEIGHT: u32 = 720;
+pub static F_WIDTH: f32 = WIDTH as f32;
+pub static F_HEIGHT: f32 = HEIGHT as f32;
+pub static F_HEIGHT_H: f32 = F_HEIGHT / 2.0;
+pub static F_WIDTH_H: f32 = F_WIDTH / 2.0;
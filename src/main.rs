@@ -1,257 +1,419 @@
-use nannou::noise::{Fbm, MultiFractal, NoiseFn, Seedable};
-use nannou::prelude::*;
-use nannou::wgpu::{BlendComponent, BlendFactor, BlendOperation};
-use std::cell::Cell;
-use std::f32;
-use std::time::{Duration, Instant};
-
-use crate::compositor::Compositor;
-use crate::render::Render;
-use crate::river::River;
+use clap::{Args, FromArgMatches, Parser, Subcommand, ValueEnum};
+use rivermap::cpu_render::CpuRenderer;
+use rivermap::floodplain::{FloodplainGrid, FloodplainLayer};
+use rivermap::glam::vec2;
+use rivermap::simulation::{DEFAULT_DT, Simulation, SnapshotClock};
+use rivermap::style::MapStyle;
+use rivermap::svg::{SvgStyle, save_svg};
+use rivermap::{F_HEIGHT, F_WIDTH, Preset, PresetFile, SimParams, apply_preset};
+use serde::Serialize;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::{fs, io};
 
+#[cfg(feature = "gui")]
 mod compositor;
+#[cfg(feature = "gui")]
+mod debug;
+#[cfg(feature = "gui")]
+mod history;
+#[cfg(feature = "gui")]
+mod panel;
+#[cfg(feature = "gui")]
 mod render;
-mod river;
-
-static WIDTH: u32 = 720;
-static HEIGHT: u32 = 720;
-static F_WIDTH: f32 = WIDTH as f32;
-static F_HEIGHT: f32 = HEIGHT as f32;
-static F_HEIGHT_H: f32 = F_HEIGHT / 2.0;
-static F_WIDTH_H: f32 = F_WIDTH / 2.0;
+#[cfg(feature = "gui")]
+mod viewer;
 
-static SLOWDOWN: f32 = 0.0;
-
-fn main() {
-    nannou::app(model).update(update).run();
+#[derive(Parser, Debug)]
+#[command(version, about = "Meandering river map generator")]
+struct Cli {
+    /// Defaults to `view`.
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-fn model(app: &App) -> Model {
-    app.set_exit_on_escape(true);
-    app.new_window()
-        .size(WIDTH, HEIGHT)
-        .msaa_samples(4)
-        .view(view)
-        .resized(resized)
-        // .key_released(key_released)
-        .build()
-        .unwrap();
-    let mut model = Model::new(app);
-    apply_preset(&mut model);
-    model
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Watch a river evolve in a window.
+    View(RunArgs),
+    /// Run one river headlessly and export it.
+    Simulate {
+        #[command(flatten)]
+        run: RunArgs,
+        #[command(flatten)]
+        export: ExportArgs,
+        /// Output file, exported as SVG, PNG or JSON based on its extension.
+        #[arg(long)]
+        out: PathBuf,
+        /// Also write the finished simulation to a save file for `--resume`.
+        #[arg(long)]
+        save: Option<PathBuf>,
+    },
+    /// Export many rivers with consecutive seeds.
+    Batch {
+        #[command(flatten)]
+        run: RunArgs,
+        #[command(flatten)]
+        export: ExportArgs,
+        /// How many maps to generate.
+        #[arg(long)]
+        count: u64,
+        /// Directory to write `map-<seed>.<format>` files into.
+        #[arg(long, default_value = ".")]
+        out_dir: PathBuf,
+        /// Format of every map.
+        #[arg(long, value_enum, default_value_t = MapFormat::Png)]
+        format: MapFormat,
+    },
+    /// List the built-in presets and those in the preset directory.
+    Presets {
+        /// Directory of `<name>.toml` preset files.
+        #[arg(long, default_value = "presets")]
+        preset_dir: PathBuf,
+    },
 }
 
-fn resized(app: &App, model: &mut Model, _size: Vec2) {
-    model.river_history = Render::new(app);
-    model.border = Render::new(app);
-    model.fill = Render::new(app);
-    let textures = [&model.river_history, &model.border, &model.fill];
-    model.compositor = Compositor::new(app, &textures);
-    model.last_history_at.set(None);
+#[derive(Args, Debug)]
+struct RunArgs {
+    /// Seed for the terrain. Random if omitted; the first seed for `batch`.
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Starting layout: a built-in name, a name in `--preset-dir`, or a
+    /// path to a preset TOML file.
+    #[arg(long, default_value = Preset::default().name())]
+    preset: String,
+    /// Directory of `<name>.toml` preset files.
+    #[arg(long, default_value = "presets")]
+    preset_dir: PathBuf,
+    /// TOML file of simulation parameters, overriding the preset's.
+    #[arg(long)]
+    params: Option<PathBuf>,
+    /// Fixed time step in seconds.
+    #[arg(long, default_value_t = DEFAULT_DT)]
+    dt: f32,
+    /// Map look: one of folded, modern or dark, or a TOML file of paper and
+    /// history colors and patterns.
+    #[arg(long)]
+    style: Option<PathBuf>,
+    /// Continue from a save file instead of starting a new river. Overrides
+    /// every other run option.
+    #[arg(long)]
+    resume: Option<PathBuf>,
 }
 
-fn update(_app: &App, model: &mut Model, mut update: Update) {
-    update.since_last = update.since_last.min(Duration::from_millis(200));
-    model.river.recompute();
-    model.river.step(update, &model.heightmap);
-    model.river.distribute();
-    model.river.tesselate(&model.widthmap);
+impl Default for RunArgs {
+    /// The options of a bare `view`, from the same defaults as the command line.
+    fn default() -> Self {
+        let command = RunArgs::augment_args(clap::Command::new("view"));
+        RunArgs::from_arg_matches(&command.get_matches_from(["view"]))
+            .expect("every run option is optional")
+    }
 }
 
-fn view(app: &App, model: &Model, mut frame: Frame) {
-    // Begin drawing
-    let draw = app.draw();
-    // for x in range(-400.0, 400.0, 10.0) {
-    //     for y in range(-400.0, 400.0, 10.0) {
-    //         let height = model.heightmap.get(vec2(x, y));
-    //         draw.rect()
-    //             .wh(vec2(10.0, 10.0))
-    //             .xy(vec2(x, y))
-    //             .color(rgb(height, height, height));
-    //     }
-    // }
-    model.draw(&draw, app, &mut frame);
-    // for &Node {
-    //     tangent,
-    //     bitangent,
-    //     loc,
-    // } in &model.river.segments
-    // {
-    //     draw.arrow()
-    //         .start(loc)
-    //         .end(loc + tangent * 10.0)
-    //         .color(BLUE);
-    //     draw.arrow()
-    //         .start(loc)
-    //         .end(loc + bitangent * 10.0)
-    //         .color(RED);
-    // }
+impl RunArgs {
+    fn simulation(&self, seed: u64) -> io::Result<Simulation> {
+        if let Some(path) = &self.resume {
+            return Simulation::load(path);
+        }
+        let params = self.params.as_ref().map(SimParams::load).transpose()?;
+        match self.preset_file()? {
+            Some(mut preset) => {
+                if let Some(params) = params {
+                    preset.params = params;
+                }
+                Ok(preset.simulation(seed, self.dt))
+            }
+            None => {
+                let preset = self
+                    .preset
+                    .parse::<Preset>()
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+                let mut sim = Simulation::new(seed, self.dt, params.unwrap_or_default());
+                apply_preset(&mut sim.river, preset);
+                Ok(sim)
+            }
+        }
+    }
+
+    /// The preset file named by `--preset`, if it isn't a built-in.
+    fn preset_file(&self) -> io::Result<Option<PresetFile>> {
+        let path = Path::new(&self.preset);
+        if path.extension().is_some_and(|e| e == "toml") {
+            return PresetFile::load(path).map(Some);
+        }
+        let in_dir = self.preset_dir.join(format!("{}.toml", self.preset));
+        if in_dir.is_file() {
+            return PresetFile::load(in_dir).map(Some);
+        }
+        Ok(None)
+    }
+
+    fn style(&self) -> io::Result<MapStyle> {
+        let Some(path) = &self.style else {
+            return Ok(MapStyle::default());
+        };
+        match path.to_str().and_then(MapStyle::named) {
+            Some(style) => Ok(style),
+            None => MapStyle::load(path),
+        }
+    }
 
-    // Write the result of our drawing to the window's frame.
-    draw.to_frame(app, &frame).unwrap();
+    fn seed(&self) -> u64 {
+        self.seed.unwrap_or_else(|| RandomState::new().hash_one(0))
+    }
 }
 
-#[derive(Debug)]
-struct Model {
-    river: river::River,
-    preset: Preset,
-    heightmap: Heightmap,
-    widthmap: Heightmap,
-    river_history: Render,
-    border: Render,
-    fill: Render,
-    last_history_at: Cell<Option<Instant>>,
-    compositor: Compositor,
+#[derive(Args, Debug)]
+struct ExportArgs {
+    /// How many fixed steps to simulate.
+    #[arg(long, default_value_t = 3000)]
+    steps: u64,
+    /// PNG width in pixels.
+    #[arg(long, default_value_t = rivermap::WIDTH)]
+    width: u32,
+    /// PNG height in pixels.
+    #[arg(long, default_value_t = rivermap::HEIGHT)]
+    height: u32,
+    /// Sim seconds between floodplain history snapshots in PNG output.
+    #[arg(long, default_value_t = 0.5)]
+    snapshot_every: f32,
+    /// Also record where and when the river flowed at every snapshot, written
+    /// next to the output as `<name>-first-visit`, `<name>-last-visit` and
+    /// `<name>-visits`.
+    #[arg(long, value_enum)]
+    floodplain: Option<FloodplainFormat>,
+    /// World units per floodplain cell.
+    #[arg(long, default_value_t = 2.0)]
+    floodplain_cell: f32,
 }
 
-impl Model {
-    pub fn new(app: &App) -> Self {
-        let river_history = Render::new(app);
-        let border = Render::new(app);
-        let fill = Render::new(app);
-        let textures = [&river_history, &border, &fill];
-        let compositor = Compositor::new(app, &textures);
+/// What `simulate` and `batch` write.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum MapFormat {
+    Svg,
+    Png,
+    Json,
+}
 
-        Model {
-            river: River::default(),
-            preset: Preset::default(),
-            heightmap: Heightmap::new(random(), 100.0),
-            widthmap: Heightmap::new(random(), 50.0),
-            last_history_at: Cell::new(None),
-            river_history,
-            border,
-            fill,
-            compositor,
-        }
+impl MapFormat {
+    /// The format named by `path`'s extension.
+    fn of(path: &Path) -> io::Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default();
+        MapFormat::from_str(extension, true).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "can't export to `{}`, use .svg, .png or .json",
+                    path.display()
+                ),
+            )
+        })
     }
 
-    pub fn draw(&self, draw: &Draw, app: &App, frame: &mut Frame) {
-        let history_fade = 1.0 / 255.0;
-        let snapshot_every = 0.5;
-        let snapshot_frac = self
-            .last_history_at
-            .get()
-            .map(|at| at.elapsed().as_secs_f32() / snapshot_every);
-        self.river_history
-            .render_frame(app, frame, |size, history| {
-                if snapshot_frac.is_none() {
-                    history.rect().wh(size).rgba(1.0, 1.0, 1.0, 1.0);
-                }
-                if snapshot_frac.is_none_or(|f| f > 1.0) {
-                    history
-                        .blend(BlendComponent {
-                            src_factor: BlendFactor::SrcAlpha,
-                            dst_factor: BlendFactor::One,
-                            operation: BlendOperation::Add,
-                        })
-                        .rect()
-                        .wh(size)
-                        .rgba(1.0, 0.0, 0.0, history_fade);
-                    self.river.draw_for_history(history);
-                    self.last_history_at.set(Some(Instant::now()));
-                }
-            });
+    fn extension(self) -> &'static str {
+        match self {
+            MapFormat::Svg => "svg",
+            MapFormat::Png => "png",
+            MapFormat::Json => "json",
+        }
+    }
+}
 
-        self.fill.render_frame(app, frame, |_, draw| {
-            draw.background().rgba(0.0, 0.0, 0.0, 0.0);
-            self.river.draw_fill(draw)
-        });
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum FloodplainFormat {
+    /// 16-bit grayscale, with times scaled to the latest visit.
+    Png,
+    /// Bare little-endian `f32` times and `u32` visit counts, row by row
+    /// from the top.
+    Raw,
+}
 
-        self.border.render_frame(app, frame, |_, draw| {
-            draw.background().rgba(0.0, 0.0, 0.0, 0.0);
-            self.river.draw_border(draw)
-        });
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
 
-        self.compositor.draw(frame);
+fn run(cli: Cli) -> io::Result<()> {
+    match cli.command {
+        None => view(RunArgs::default()),
+        Some(Command::View(run)) => view(run),
+        Some(Command::Simulate {
+            run,
+            export,
+            out,
+            save,
+        }) => {
+            let seed = run.seed();
+            let sim = export_map(run.simulation(seed)?, &export, &run.style()?, &out)?;
+            println!("{} (seed {})", out.display(), sim.seed);
+            if let Some(save) = save {
+                sim.save(&save)?;
+                println!("{}", save.display());
+            }
+            Ok(())
+        }
+        Some(Command::Batch {
+            run,
+            export,
+            count,
+            out_dir,
+            format,
+        }) => {
+            fs::create_dir_all(&out_dir)?;
+            let first = run.seed();
+            let style = run.style()?;
+            for seed in (0..count).map(|i| first.wrapping_add(i)) {
+                let out = out_dir.join(format!("map-{seed}.{}", format.extension()));
+                export_map(run.simulation(seed)?, &export, &style, &out)?;
+                println!("{}", out.display());
+            }
+            Ok(())
+        }
+        Some(Command::Presets { preset_dir }) => {
+            for preset in Preset::ALL {
+                println!("{}", preset.name());
+            }
+            if preset_dir.is_dir() {
+                for (name, _) in PresetFile::load_dir(&preset_dir)? {
+                    println!("{name}");
+                }
+            }
+            Ok(())
+        }
     }
 }
 
-#[derive(Clone, Debug)]
-struct Heightmap {
-    perlin: Fbm,
-    scale: f64,
+#[cfg(feature = "gui")]
+fn view(run: RunArgs) -> io::Result<()> {
+    let sim = run.simulation(run.seed())?;
+    let style = run.style()?;
+    viewer::run(viewer::ViewOptions { sim, style });
+    Ok(())
+}
+
+#[cfg(not(feature = "gui"))]
+fn view(_: RunArgs) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "rivermap was built without the `gui` feature",
+    ))
 }
 
-impl Heightmap {
-    fn new(seed: u32, scale: f32) -> Self {
-        Heightmap {
-            perlin: Fbm::new().set_octaves(6).set_seed(seed),
-            scale: scale as f64,
+/// Runs `sim` for the requested number of steps and writes it to `out`.
+fn export_map(
+    mut sim: Simulation,
+    export: &ExportArgs,
+    style: &MapStyle,
+    out: &Path,
+) -> io::Result<Simulation> {
+    let format = MapFormat::of(out)?;
+    let mut renderer = (format == MapFormat::Png).then(|| {
+        let mut renderer = CpuRenderer::new(export.width, export.height);
+        renderer.style = style.clone();
+        renderer.snapshot_every = export.snapshot_every;
+        renderer
+    });
+    let mut floodplain = export
+        .floodplain
+        .map(|_| FloodplainGrid::new(vec2(F_WIDTH, F_HEIGHT), export.floodplain_cell));
+    let mut snapshots = SnapshotClock::default();
+    for _ in 0..export.steps {
+        sim.step();
+        if snapshots.due(sim.time(), export.snapshot_every) {
+            if let Some(renderer) = &mut renderer {
+                renderer.snapshot(&sim.river);
+            }
+            if let Some(floodplain) = &mut floodplain {
+                floodplain.snapshot(&sim.river);
+            }
         }
     }
-    pub fn get(&self, xy: Vec2) -> f32 {
-        if Heightmap::in_bounds(xy) {
-            self.perlin.get((xy.as_f64() / self.scale).to_array()) as f32
-        } else {
-            1.0
+
+    match format {
+        MapFormat::Png => renderer.unwrap().save_png(&sim.river, out)?,
+        MapFormat::Svg => save_svg(&sim.river, &SvgStyle::default(), out)?,
+        MapFormat::Json => {
+            let json =
+                serde_json::to_string_pretty(&MapJson::new(&sim)).map_err(io::Error::other)?;
+            fs::write(out, json)?;
         }
     }
+    if let (Some(floodplain), Some(format)) = (floodplain, export.floodplain) {
+        save_floodplain(&floodplain, format, out)?;
+    }
+    Ok(sim)
+}
 
-    fn in_bounds(xy: Vec2) -> bool {
-        xy.x < F_WIDTH_H && xy.x > -F_WIDTH_H && xy.y < F_HEIGHT_H && xy.y > -F_HEIGHT_H
+/// Writes every layer of `floodplain` next to `out`, named after it.
+fn save_floodplain(
+    floodplain: &FloodplainGrid,
+    format: FloodplainFormat,
+    out: &Path,
+) -> io::Result<()> {
+    let stem = out.file_stem().unwrap_or_default().to_string_lossy();
+    for layer in FloodplainLayer::ALL {
+        let extension = match format {
+            FloodplainFormat::Png => "png",
+            FloodplainFormat::Raw => "bin",
+        };
+        let path = out.with_file_name(format!("{stem}-{}.{extension}", layer.name()));
+        match format {
+            FloodplainFormat::Png => floodplain.save_png(layer, &path)?,
+            FloodplainFormat::Raw => floodplain.save_raw(layer, &path)?,
+        }
+        println!(
+            "{} ({}x{} cells)",
+            path.display(),
+            floodplain.columns(),
+            floodplain.rows()
+        );
     }
+    Ok(())
 }
 
-#[derive(Copy, Clone, Debug, Default)]
-pub enum Preset {
-    CIRCLE,
-    #[default]
-    ACROSS,
+/// Plain geometry of a finished map, for pipelines that post-process it.
+#[derive(Serialize)]
+struct MapJson {
+    seed: u64,
+    steps: u64,
+    time: f64,
+    closed: bool,
+    centerline: Vec<[f32; 2]>,
+    left_bank: Vec<[f32; 2]>,
+    right_bank: Vec<[f32; 2]>,
+    oxbows: Vec<Vec<[f32; 2]>>,
 }
 
-fn apply_preset(model: &mut Model) {
-    model.river.segments.clear();
-    match model.preset {
-        Preset::CIRCLE => {
-            // model.river.closed = true;
-            let smaller_side = F_WIDTH.min(F_HEIGHT);
-            let radius = 0.3 * smaller_side;
-            let circumference = radius * 2.0 * PI;
-            let num_steps = (circumference / river::MIN_DISTANCE).ceil() as usize;
-            for i in 0..num_steps {
-                let theta = (i as f32 / num_steps as f32) * 2.0 * f32::consts::PI;
-                let (x, y) = theta.sin_cos();
-                let node = river::Node {
-                    loc: vec2(x * radius, y * radius),
-                    color: lin_srgba(1.0, 0.2, 0.2, 1.0),
-                    ..Default::default()
-                };
-                if i == 0 {
-                    model.river.start = node;
-                } else if i == num_steps - 1 {
-                    model.river.end = node;
-                } else {
-                    model.river.segments.push(node);
-                }
-            }
-        }
-        Preset::ACROSS => {
-            model.river.closed = false;
-            for i in 0..500 {
-                let t = (i as f32 / 500.0) * 2.0 - 1.0;
-                let x = t;
-                let y = 0.1 * (t * 20.0).sin();
-                let node = river::Node {
-                    loc: vec2(x * F_WIDTH_H + 0.1, y * F_HEIGHT_H),
-                    color: lin_srgba(0.0, 0.0, 0.0, 1.0),
-                    // color: lin_srgba(1.0, 0.2, 0.2, 1.0),
-                    ..Default::default()
-                };
-                if i == 0 {
-                    model.river.start = node;
-                } else if i == 499 {
-                    model.river.end = node;
-                } else {
-                    model.river.segments.push(node);
-                }
-            }
+impl MapJson {
+    fn new(sim: &Simulation) -> Self {
+        let [left_bank, right_bank] = sim
+            .river
+            .banks()
+            .map(|bank| bank.iter().map(|p| p.to_array()).collect());
+        MapJson {
+            seed: sim.seed,
+            steps: sim.steps,
+            time: sim.time(),
+            closed: sim.river.closed,
+            centerline: sim.river.nodes().map(|n| n.loc.to_array()).collect(),
+            left_bank,
+            right_bank,
+            oxbows: sim
+                .river
+                .oxbows
+                .iter()
+                .map(|lake| lake.outline().iter().map(|p| p.to_array()).collect())
+                .collect(),
         }
     }
 }
-
-// fn range(start: f32, threshold: f32, step_size: f32) -> impl Iterator<Item = f32> {
-//     std::iter::successors(Some(start), move |&prev| {
-//         let next = prev + step_size;
-//         (next < threshold).then_some(next)
-//     })
-// }
@@ -0,0 +1,103 @@
+use crate::{Node, RiverMeshBuilder, SimParams, Terrain};
+use glam::Vec2;
+use lyon::tessellation::{self as tes, GeometryBuilder, StrokeTessellator};
+#[cfg(feature = "gui")]
+use nannou::prelude::{Draw, LinSrgba, lin_srgba};
+
+const MIN_WIDTH: f32 = 0.05;
+
+/// An abandoned meander loop, cut off from the main channel by
+/// [`River::distribute`](crate::River::distribute).
+///
+/// The lake keeps the centerline of the loop it replaced and narrows by
+/// [`SimParams::oxbow_fill_rate`] every simulated second until it silts up.
+#[derive(Clone, Debug)]
+pub struct OxbowLake {
+    pub nodes: Vec<Node>,
+    /// Sim time at which the loop was cut off.
+    pub created_at: f64,
+    lake_builder: RiverMeshBuilder,
+}
+
+impl OxbowLake {
+    pub fn new(nodes: Vec<Node>, created_at: f64) -> Self {
+        OxbowLake {
+            nodes,
+            created_at,
+            lake_builder: RiverMeshBuilder::default(),
+        }
+    }
+
+    pub fn age(&self, time: f64) -> f32 {
+        (time - self.created_at) as f32
+    }
+
+    /// Closed polygon around the lake, left bank then right bank reversed.
+    pub fn outline(&self) -> Vec<Vec2> {
+        let left = self.lake_builder.left_bank().iter().map(|p| p.1);
+        let right = self.lake_builder.right_bank().iter().rev().map(|p| p.1);
+        left.chain(right).collect()
+    }
+
+    pub fn mesh(&self) -> &RiverMeshBuilder {
+        &self.lake_builder
+    }
+
+    /// Rebuilds the lake mesh at the given sim time, returning `false` once
+    /// the lake has completely filled in.
+    pub fn tesselate(&mut self, time: f64, widthmap: &dyn Terrain, params: &SimParams) -> bool {
+        self.lake_builder.abort_geometry();
+
+        let filled = self.age(time) * params.oxbow_fill_rate;
+        let getwidth = |p| params.width_at(widthmap, p) - filled;
+        if self.nodes.iter().all(|n| getwidth(n.loc) <= 0.0) {
+            return false;
+        }
+        // Lyon produces NaN vertices for zero width strokes, so keep dry stretches a hair wide.
+        let getwidth = |p| getwidth(p).max(MIN_WIDTH);
+
+        let mut path_builder = lyon::path::Path::builder_with_attributes(5);
+        let mut nodes = self.nodes.iter();
+        if let Some(first) = nodes.next() {
+            let (p, a) = first.lyonize(getwidth(first.loc));
+            path_builder.begin(p, a.as_ref());
+        }
+        for n in nodes {
+            let (p, a) = n.lyonize(getwidth(n.loc));
+            path_builder.line_to(p, a.as_ref());
+        }
+        path_builder.end(false);
+        let path = path_builder.build();
+
+        let mut tessellator = StrokeTessellator::new();
+        let mut opts = tes::StrokeOptions::default();
+        opts.variable_line_width = Some(0);
+        tessellator
+            .tessellate_path(&path, &opts, &mut self.lake_builder)
+            .unwrap();
+        true
+    }
+}
+
+#[cfg(feature = "gui")]
+impl OxbowLake {
+    pub fn draw_fill(&self, draw: &Draw) {
+        draw.mesh()
+            .indexed_colored(
+                self.lake_builder
+                    .vertices()
+                    .iter()
+                    .map(|&(p, c)| (p, lin_srgba(c.x, c.y, c.z, c.w))),
+                self.lake_builder.indices().iter().copied(),
+            )
+            .finish();
+    }
+
+    pub fn draw_border(&self, draw: &Draw, ink: LinSrgba) {
+        let mut outline = self.outline();
+        if let Some(&first) = outline.first() {
+            outline.push(first);
+        }
+        draw.polyline().weight(1.0).color(ink).points(outline);
+    }
+}
@@ -0,0 +1,153 @@
+use crate::debug::DebugLayers;
+use nannou_egui::egui::{self, Ui};
+use rivermap::params::{HeightmapParams, MigrationModel};
+use rivermap::simulation::Simulation;
+use rivermap::style::MapStyle;
+use std::ops::RangeInclusive;
+
+/// Settings that belong to the viewer rather than the simulation.
+#[derive(Clone, Debug)]
+pub struct ViewSettings {
+    /// Sim seconds between floodplain history snapshots.
+    pub history_every: f32,
+    pub debug: DebugLayers,
+    pub style: MapStyle,
+}
+
+/// Shows the parameter window and applies any edits to `sim` right away.
+pub fn show(ctx: &egui::Context, sim: &mut Simulation, view: &mut ViewSettings) {
+    egui::Window::new("Parameters")
+        .default_width(280.0)
+        .show(ctx, |ui| {
+            let mut params = sim.river.params;
+            let (mut height_seed, mut width_seed) = (sim.height_seed, sim.width_seed);
+
+            ui.collapsing("Migration", |ui| {
+                ui.horizontal(|ui| {
+                    ui.selectable_value(&mut params.migration, MigrationModel::Drift, "Drift");
+                    ui.selectable_value(
+                        &mut params.migration,
+                        MigrationModel::Curvature,
+                        "Curvature",
+                    );
+                });
+                slider(ui, &mut params.speed, 0.0..=10.0, "speed");
+                slider(ui, &mut params.tangent_weight, 0.0..=50.0, "tangent weight");
+                slider(
+                    ui,
+                    &mut params.bitangent_weight,
+                    0.0..=20.0,
+                    "bitangent weight",
+                );
+                slider(
+                    ui,
+                    &mut params.gradient_weight,
+                    0.0..=300.0,
+                    "gradient weight",
+                );
+                if params.migration == MigrationModel::Curvature {
+                    slider(ui, &mut params.migration_rate, 0.0..=30.0, "migration rate");
+                    slider(
+                        ui,
+                        &mut params.local_curvature_weight,
+                        -5.0..=5.0,
+                        "local curvature",
+                    );
+                    slider(
+                        ui,
+                        &mut params.upstream_curvature_weight,
+                        -5.0..=5.0,
+                        "upstream curvature",
+                    );
+                    slider(
+                        ui,
+                        &mut params.upstream_length,
+                        1.0..=300.0,
+                        "upstream length",
+                    );
+                }
+            });
+
+            ui.collapsing("Spacing", |ui| {
+                slider(ui, &mut params.point_spacing, 1.0..=20.0, "point spacing");
+                slider(ui, &mut params.min_distance, 2.0..=60.0, "min distance");
+            });
+
+            ui.collapsing("Width", |ui| {
+                ui.label("widthmap × scale + base");
+                slider(ui, &mut params.width_scale, 0.0..=40.0, "scale");
+                slider(ui, &mut params.width_base, 1.0..=40.0, "base");
+                slider(
+                    ui,
+                    &mut params.oxbow_fill_rate,
+                    0.0..=5.0,
+                    "oxbow fill rate",
+                );
+            });
+
+            ui.collapsing("Heightmap", |ui| {
+                noise(ui, &mut height_seed, &mut params.heightmap);
+            });
+            ui.collapsing("Widthmap", |ui| {
+                noise(ui, &mut width_seed, &mut params.widthmap);
+            });
+
+            ui.collapsing("History", |ui| {
+                slider(
+                    ui,
+                    &mut view.history_every,
+                    0.05..=5.0,
+                    "snapshot every (sim s)",
+                );
+                let history = &mut view.style.history;
+                slider(ui, &mut history.max_age, 1.0..=1000.0, "max age");
+                slider(
+                    ui,
+                    &mut history.pattern_scale,
+                    0.002..=0.05,
+                    "pattern scale",
+                );
+            });
+
+            ui.collapsing("Paper", |ui| {
+                let paper = &mut view.style.paper;
+                ui.checkbox(&mut paper.plain, "plain");
+                ui.add_enabled_ui(!paper.plain, |ui| {
+                    slider(ui, &mut paper.folds[0], 0.0..=8.0, "folds across");
+                    slider(ui, &mut paper.folds[1], 0.0..=8.0, "folds down");
+                    slider(ui, &mut paper.vignette, 0.0..=2.0, "vignette");
+                    ui.add(egui::Slider::new(&mut paper.stains, 0..=8).text("stains"));
+                });
+            });
+
+            ui.collapsing("Debug", |ui| {
+                let debug = &mut view.debug;
+                ui.checkbox(&mut debug.enabled, "overlay (G)");
+                ui.add_enabled_ui(debug.enabled, |ui| {
+                    ui.checkbox(&mut debug.frames, "tangents and bitangents");
+                    ui.checkbox(&mut debug.gradients, "downhill pull");
+                    ui.checkbox(&mut debug.cutoffs, "cutoff candidates");
+                    ui.checkbox(&mut debug.widths, "widths");
+                    ui.checkbox(&mut debug.terrain, "terrain");
+                });
+            });
+
+            if params != sim.river.params {
+                sim.set_params(params);
+            }
+            sim.set_seeds(height_seed, width_seed);
+        });
+}
+
+fn slider(ui: &mut Ui, value: &mut f32, range: RangeInclusive<f32>, text: &str) {
+    ui.add(egui::Slider::new(value, range).text(text));
+}
+
+fn noise(ui: &mut Ui, seed: &mut u32, params: &mut HeightmapParams) {
+    ui.horizontal(|ui| {
+        ui.add(egui::DragValue::new(seed));
+        ui.label("seed");
+    });
+    slider(ui, &mut params.scale, 10.0..=500.0, "scale");
+    ui.add(egui::Slider::new(&mut params.octaves, 1..=10).text("octaves"));
+}
@@ -0,0 +1,142 @@
+use crate::Terrain;
+use crate::force::{BankMigration, BitangentPull, Downhill, Force, TangentPush};
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+use std::{fs, io, path::Path};
+
+/// Tunable constants for a simulation run.
+///
+/// Missing fields fall back to their defaults when deserializing, so a
+/// parameter file only needs to list what it changes.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SimParams {
+    /// Nodes closer than this (and far enough apart along the river) cause a cutoff.
+    pub min_distance: f32,
+    /// Distance between nodes after [`River::distribute`](crate::River::distribute).
+    pub point_spacing: f32,
+    /// Subtracted from the time step before moving nodes.
+    pub slowdown: f32,
+    /// How strongly nodes push along the river.
+    pub tangent_weight: f32,
+    /// How strongly nodes are pushed away from the inside of their bend,
+    /// toward the outer bank.
+    pub bitangent_weight: f32,
+    /// How strongly nodes roll down the terrain gradient.
+    ///
+    /// Defaults to 70, twice the old 35, because [`Terrain::gradient`] now
+    /// halves its central difference to give a true slope.
+    pub gradient_weight: f32,
+    /// Overall multiplier on node motion.
+    pub speed: f32,
+    /// Which built-in forces a new river starts with.
+    pub migration: MigrationModel,
+    /// Bank migration speed per unit of width-scaled curvature, used by
+    /// [`MigrationModel::Curvature`].
+    pub migration_rate: f32,
+    /// Weight of the curvature at the node itself. Negative values straighten.
+    pub local_curvature_weight: f32,
+    /// Weight of the upstream-averaged curvature.
+    pub upstream_curvature_weight: f32,
+    /// Decay length of the upstream curvature average, in world units.
+    pub upstream_length: f32,
+    /// River width is `widthmap * width_scale + width_base`.
+    pub width_scale: f32,
+    pub width_base: f32,
+    /// How many world units of width an oxbow lake loses per second.
+    pub oxbow_fill_rate: f32,
+    pub heightmap: HeightmapParams,
+    pub widthmap: HeightmapParams,
+}
+
+impl Default for SimParams {
+    fn default() -> Self {
+        SimParams {
+            min_distance: 15.0,
+            point_spacing: 5.0,
+            slowdown: 0.0,
+            tangent_weight: 10.0,
+            bitangent_weight: 4.0,
+            gradient_weight: 70.0,
+            speed: 3.0,
+            migration: MigrationModel::Drift,
+            migration_rate: 10.0,
+            local_curvature_weight: -1.0,
+            upstream_curvature_weight: 2.5,
+            upstream_length: 60.0,
+            width_scale: 10.0,
+            width_base: 15.0,
+            oxbow_fill_rate: 0.5,
+            heightmap: HeightmapParams {
+                scale: 100.0,
+                octaves: 6,
+            },
+            widthmap: HeightmapParams {
+                scale: 50.0,
+                octaves: 6,
+            },
+        }
+    }
+}
+
+impl SimParams {
+    /// River width at `xy`, from `widthmap * width_scale + width_base`.
+    pub fn width_at(&self, widthmap: &dyn Terrain, xy: Vec2) -> f32 {
+        widthmap.height(xy) * self.width_scale + self.width_base
+    }
+
+    pub fn from_toml(text: &str) -> io::Result<Self> {
+        toml::from_str(text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).expect("SimParams always serializes")
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_toml(&fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_toml())
+    }
+}
+
+/// A built-in set of [`Force`]s for moving river nodes.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationModel {
+    /// Fixed mix of tangent push, bitangent pull and terrain gradient.
+    #[default]
+    Drift,
+    /// Bank migration driven by upstream-weighted curvature.
+    Curvature,
+}
+
+impl MigrationModel {
+    pub fn forces(self) -> Vec<Box<dyn Force>> {
+        match self {
+            MigrationModel::Drift => vec![
+                Box::new(TangentPush),
+                Box::new(BitangentPull),
+                Box::new(Downhill),
+            ],
+            MigrationModel::Curvature => vec![Box::new(BankMigration), Box::new(Downhill)],
+        }
+    }
+}
+
+/// Noise settings for a [`Heightmap`](crate::Heightmap).
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeightmapParams {
+    /// World units per noise period.
+    pub scale: f32,
+    pub octaves: usize,
+}
+
+impl Default for HeightmapParams {
+    fn default() -> Self {
+        SimParams::default().heightmap
+    }
+}
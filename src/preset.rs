@@ -0,0 +1,313 @@
+use crate::simulation::split_seed;
+use crate::{F_HEIGHT, F_HEIGHT_H, F_WIDTH, F_WIDTH_H, Node, River, SimParams, Simulation};
+use glam::{Vec2, Vec4, vec2, vec4};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+use std::str::FromStr;
+use std::{fs, io, path::Path};
+
+/// Built-in starting shapes for a river.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Preset {
+    CIRCLE,
+    #[default]
+    ACROSS,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 2] = [Preset::CIRCLE, Preset::ACROSS];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::CIRCLE => "circle",
+            Preset::ACROSS => "across",
+        }
+    }
+}
+
+impl FromStr for Preset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Preset::ALL
+            .into_iter()
+            .find(|p| p.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown preset `{s}`, expected one of: circle, across"))
+    }
+}
+
+/// Replaces the river's nodes with the preset's starting shape.
+pub fn apply_preset(river: &mut River, preset: Preset) {
+    river.segments.clear();
+    match preset {
+        Preset::CIRCLE => {
+            river.closed = true;
+            let smaller_side = F_WIDTH.min(F_HEIGHT);
+            let radius = 0.3 * smaller_side;
+            let circumference = radius * 2.0 * PI;
+            let num_steps = (circumference / river.params.min_distance).ceil() as usize;
+            for i in 0..num_steps {
+                let theta = (i as f32 / num_steps as f32) * 2.0 * PI;
+                let (x, y) = theta.sin_cos();
+                let node = Node {
+                    loc: vec2(x * radius, y * radius),
+                    color: vec4(1.0, 0.2, 0.2, 1.0),
+                    ..Default::default()
+                };
+                if i == 0 {
+                    river.start = node;
+                } else if i == num_steps - 1 {
+                    river.end = node;
+                } else {
+                    river.segments.push(node);
+                }
+            }
+        }
+        Preset::ACROSS => {
+            river.closed = false;
+            for i in 0..500 {
+                let t = (i as f32 / 500.0) * 2.0 - 1.0;
+                let x = t;
+                let y = 0.1 * (t * 20.0).sin();
+                let node = Node {
+                    loc: vec2(x * F_WIDTH_H + 0.1, y * F_HEIGHT_H),
+                    color: vec4(0.0, 0.0, 0.0, 1.0),
+                    // color: vec4(1.0, 0.2, 0.2, 1.0),
+                    ..Default::default()
+                };
+                if i == 0 {
+                    river.start = node;
+                } else if i == 499 {
+                    river.end = node;
+                } else {
+                    river.segments.push(node);
+                }
+            }
+        }
+    }
+}
+
+/// A starting layout loaded from a TOML file.
+///
+/// ```toml
+/// closed = false
+/// color = [0.0, 0.0, 0.0, 1.0]
+/// heightmap_seed = 7
+///
+/// [shape]
+/// kind = "sine"
+/// from = [-360.0, 0.0]
+/// to = [360.0, 0.0]
+/// amplitude = 36.0
+/// waves = 3.0
+/// count = 500
+///
+/// [params]
+/// tangent_weight = 8.0
+/// heightmap = { scale = 150.0 }
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PresetFile {
+    pub closed: bool,
+    /// Linear RGBA for every node, unless the shape gives its own colors.
+    pub color: [f32; 4],
+    /// Fixed noise seeds, instead of deriving them from the run seed.
+    pub heightmap_seed: Option<u32>,
+    pub widthmap_seed: Option<u32>,
+    pub params: SimParams,
+    pub shape: Shape,
+}
+
+impl Default for PresetFile {
+    fn default() -> Self {
+        PresetFile {
+            closed: false,
+            color: [0.0, 0.0, 0.0, 1.0],
+            heightmap_seed: None,
+            widthmap_seed: None,
+            params: SimParams::default(),
+            shape: Shape::Points {
+                points: Vec::new(),
+                colors: None,
+            },
+        }
+    }
+}
+
+/// Initial centerline of a [`PresetFile`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Shape {
+    /// Explicit nodes, optionally each with its own linear RGBA color.
+    Points {
+        points: Vec<[f32; 2]>,
+        colors: Option<Vec<[f32; 4]>>,
+    },
+    /// A sine wave running from `from` toward `to`.
+    Sine {
+        from: [f32; 2],
+        to: [f32; 2],
+        amplitude: f32,
+        /// Full periods between `from` and `to`.
+        waves: f32,
+        count: usize,
+    },
+    /// A ring, usually paired with `closed = true`. Nodes are spaced
+    /// [`SimParams::min_distance`] apart unless `count` is given.
+    Circle {
+        center: [f32; 2],
+        radius: f32,
+        count: Option<usize>,
+    },
+}
+
+impl PresetFile {
+    pub fn from_toml(text: &str) -> io::Result<Self> {
+        toml::from_str(text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_toml(&fs::read_to_string(path)?)
+    }
+
+    /// Every `*.toml` preset in `dir`, keyed by file stem and sorted by name.
+    pub fn load_dir(dir: impl AsRef<Path>) -> io::Result<Vec<(String, PresetFile)>> {
+        let mut presets = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == "toml")
+                && let Some(name) = path.file_stem().and_then(|s| s.to_str())
+            {
+                presets.push((name.to_owned(), PresetFile::load(&path)?));
+            }
+        }
+        presets.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(presets)
+    }
+
+    /// Centerline node positions and colors.
+    pub fn nodes(&self) -> Vec<Node> {
+        let color = Vec4::from(self.color);
+        let node = |loc: Vec2, color: Vec4| Node {
+            loc,
+            color,
+            ..Default::default()
+        };
+        match &self.shape {
+            Shape::Points { points, colors } => points
+                .iter()
+                .enumerate()
+                .map(|(i, &p)| {
+                    let c = colors
+                        .as_ref()
+                        .and_then(|c| c.get(i))
+                        .map_or(color, |&c| c.into());
+                    node(p.into(), c)
+                })
+                .collect(),
+            &Shape::Sine {
+                from,
+                to,
+                amplitude,
+                waves,
+                count,
+            } => {
+                let (from, to) = (Vec2::from(from), Vec2::from(to));
+                let normal = (to - from).normalize_or_zero().perp();
+                (0..count)
+                    .map(|i| {
+                        let t = i as f32 / count.saturating_sub(1).max(1) as f32;
+                        let wave = (t * waves * 2.0 * PI).sin() * amplitude;
+                        node(from.lerp(to, t) + normal * wave, color)
+                    })
+                    .collect()
+            }
+            &Shape::Circle {
+                center,
+                radius,
+                count,
+            } => {
+                let count = count.unwrap_or_else(|| {
+                    (radius * 2.0 * PI / self.params.min_distance).ceil() as usize
+                });
+                (0..count)
+                    .map(|i| {
+                        let theta = (i as f32 / count as f32) * 2.0 * PI;
+                        let (x, y) = theta.sin_cos();
+                        node(Vec2::from(center) + vec2(x, y) * radius, color)
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Starts a simulation from this layout. Noise seeds not fixed by the
+    /// preset are derived from `seed` as in [`Simulation::new`].
+    pub fn simulation(&self, seed: u64, dt: f32) -> Simulation {
+        let (height_seed, width_seed) = split_seed(seed);
+        let mut sim = Simulation::with_seeds(
+            seed,
+            self.heightmap_seed.unwrap_or(height_seed),
+            self.widthmap_seed.unwrap_or(width_seed),
+            dt,
+            self.params,
+        );
+        let river = &mut sim.river;
+        river.closed = self.closed;
+        let mut nodes = self.nodes();
+        if nodes.len() >= 2 {
+            river.end = nodes.pop().unwrap();
+            river.start = nodes.remove(0);
+        }
+        river.segments = nodes;
+        sim
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::DEFAULT_DT;
+
+    #[test]
+    fn shipped_presets_load() {
+        let presets =
+            PresetFile::load_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/presets")).unwrap();
+        assert!(!presets.is_empty());
+        for (name, preset) in presets {
+            let sim = preset.simulation(1, DEFAULT_DT);
+            assert_eq!(sim.river.closed, preset.closed, "{name}");
+            assert!(sim.river.segments.len() > 2, "{name}");
+        }
+    }
+
+    #[test]
+    fn doc_example_parses() {
+        // The example in the `PresetFile` docs.
+        let preset = PresetFile::from_toml(
+            r#"
+closed = false
+color = [0.0, 0.0, 0.0, 1.0]
+heightmap_seed = 7
+
+[shape]
+kind = "sine"
+from = [-360.0, 0.0]
+to = [360.0, 0.0]
+amplitude = 36.0
+waves = 3.0
+count = 500
+
+[params]
+tangent_weight = 8.0
+heightmap = { scale = 150.0 }
+"#,
+        )
+        .unwrap();
+        assert_eq!(preset.heightmap_seed, Some(7));
+        assert_eq!(preset.params.tangent_weight, 8.0);
+        assert_eq!(preset.params.heightmap.scale, 150.0);
+        assert_eq!(preset.nodes().len(), 500);
+    }
+}
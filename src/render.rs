@@ -3,13 +3,16 @@ use nannou::{
     wgpu::{Texture, TextureBuilder},
 };
 
+/// An offscreen target drawn in points, centered on the origin like the window.
 #[derive(Clone, Debug)]
 pub struct Render {
     pub texture: Texture,
+    /// Pixels per point, fixed when the target is created.
     scale: f32,
 }
 
 impl Render {
+    /// A target covering the window at its current size and DPI.
     pub fn new(app: &App) -> Self {
         let (w, h) = app.main_window().inner_size_pixels();
         let scale = app.main_window().scale_factor();
@@ -1,31 +1,58 @@
-use crate::{Heightmap, SLOWDOWN};
+use crate::force::{Environment, Force};
+use crate::spatial::SpatialGrid;
+use crate::{OxbowLake, SimParams, Terrain};
+use glam::{Vec2, Vec3, Vec4, vec2, vec3, vec4};
 use lyon::tessellation::{self as tes, GeometryBuilder};
-use nannou::prelude::*;
-use nannou::{event::Update, glam::Vec2};
+#[cfg(feature = "gui")]
+use nannou::prelude::{Draw, LinSrgba, lin_srgba};
+use std::iter;
 use tes::StrokeTessellator;
 
-pub static MIN_DISTANCE: f32 = 15.0;
-pub static POINT_SPACING: f32 = 5.0;
-
 #[derive(Copy, Clone, Debug, Default)]
 pub struct Node {
     pub loc: Vec2,
     pub tangent: Vec2,
     pub bitangent: Vec2,
-    pub color: LinSrgba,
+    /// Signed curvature of the centerline, positive when turning left.
+    pub curvature: f32,
+    /// Curvature averaged upstream with an exponential kernel of length
+    /// [`SimParams::upstream_length`].
+    pub upstream_curvature: f32,
+    /// Linear RGBA.
+    pub color: Vec4,
 }
 
 impl Node {
-    pub fn step(&mut self, update: Update, heightmap: &Heightmap) {
-        let up = heightmap.get(self.loc + vec2(1.0, 0.0));
-        let down = heightmap.get(self.loc + vec2(-1.0, 0.0));
-        let left = heightmap.get(self.loc + vec2(0.0, -1.0));
-        let right = heightmap.get(self.loc + vec2(0.0, 1.0));
-        let grad = -vec2(up - down, right - left);
-        //self.loc += (self.tangent * 0.0 + -self.bitangent * 0.0 + grad * 35.0)
-        self.loc += (self.tangent * 10.0 + -self.bitangent * 4.0 + grad * 35.0)
-            * (update.since_last.as_secs_f32() - SLOWDOWN)
-            * 3.0;
+    /// A freshly placed node whose frame is filled in by the next [`River::recompute`].
+    fn resampled(loc: Vec2, color: Vec4) -> Self {
+        Node {
+            loc,
+            tangent: vec2(f32::NAN, f32::NAN),
+            bitangent: vec2(f32::NAN, f32::NAN),
+            curvature: f32::NAN,
+            upstream_curvature: f32::NAN,
+            color,
+        }
+    }
+
+    /// Recomputes the tangent frame and curvature from the neighboring node locations.
+    fn set_frame(&mut self, prev: Vec2, next: Vec2) {
+        let (a, b, c) = (prev, self.loc, next);
+        let (tangent, cross) = (
+            (c - a).normalize_or_zero(),
+            (b - a)
+                .normalize_or_zero()
+                .perp_dot((c - b).normalize_or_zero()),
+        );
+        self.tangent = tangent;
+        self.bitangent = (tangent.perp() * cross.signum()).normalize_or_zero();
+        // Menger curvature of the circle through a, b and c.
+        let chord = (c - a).length();
+        self.curvature = if chord > 0.0 {
+            2.0 * cross / chord
+        } else {
+            0.0
+        };
     }
 
     pub fn lyonize(&self, width: f32) -> (lyon::path::math::Point, impl AsRef<[f32]>) {
@@ -33,60 +60,184 @@ impl Node {
             tes::geom::point(self.loc.x, self.loc.y),
             [
                 width,
-                self.color.red,
-                self.color.green,
-                self.color.blue,
-                self.color.alpha,
+                self.color.x,
+                self.color.y,
+                self.color.z,
+                self.color.w,
             ],
         )
     }
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug)]
 pub struct River {
     pub start: Node,
     pub segments: Vec<Node>,
     pub end: Node,
     pub closed: bool,
+    pub params: SimParams,
+    /// Everything that moves the nodes, summed every [`River::step`].
+    pub forces: Vec<Box<dyn Force>>,
+    /// Sim time in seconds, advanced by [`River::step`].
+    pub time: f64,
+    /// Loops cut off by [`River::distribute`], oldest first.
+    pub oxbows: Vec<OxbowLake>,
     pub river_builder: RiverMeshBuilder,
 }
 
+impl Default for River {
+    fn default() -> Self {
+        let params = SimParams::default();
+        River {
+            start: Node::default(),
+            segments: Vec::new(),
+            end: Node::default(),
+            closed: false,
+            params,
+            forces: params.migration.forces(),
+            time: 0.0,
+            oxbows: Vec::new(),
+            river_builder: RiverMeshBuilder::default(),
+        }
+    }
+}
+
 impl River {
+    /// Looks up a segment by index.
+    ///
+    /// Open rivers return `None` past either end. Closed rivers treat
+    /// `start`, `segments` and `end` as one ring, where `-1` is `start`,
+    /// `segments.len()` is `end` and every other index wraps around.
     pub fn node(&self, i: isize) -> Option<Node> {
-        if i > 0 {
+        if self.closed {
+            let len = self.segments.len() as isize;
+            match (i + 1).rem_euclid(len + 2) - 1 {
+                -1 => Some(self.start),
+                i if i == len => Some(self.end),
+                i => Some(self.segments[i as usize]),
+            }
+        } else if i >= 0 {
             self.segments.get(i as usize).copied()
         } else {
             None
         }
     }
 
+    /// Every node in flow order, including the endpoints.
+    pub fn nodes(&self) -> impl Iterator<Item = &Node> {
+        iter::once(&self.start)
+            .chain(&self.segments)
+            .chain(iter::once(&self.end))
+    }
+
+    /// Replaces the channel with an open river along the polyline `points`,
+    /// resampled every [`SimParams::point_spacing`]. Oxbow lakes are kept.
+    pub fn set_centerline(&mut self, points: &[Vec2], color: Vec4) {
+        let (Some(&first), Some(&last)) = (points.first(), points.last()) else {
+            return;
+        };
+        let node = |loc| Node {
+            loc,
+            color,
+            ..Default::default()
+        };
+        let point_spacing = self.params.point_spacing;
+        let mut segments = Vec::new();
+        let mut distance_to_next_point = point_spacing;
+        for line in points.windows(2) {
+            let mut at_loc = line[0];
+            let mut still_to_go = at_loc.distance(line[1]);
+            if still_to_go == 0.0 {
+                continue;
+            }
+            let direction = (line[1] - at_loc) / still_to_go;
+            while still_to_go >= distance_to_next_point {
+                at_loc += direction * distance_to_next_point;
+                still_to_go -= distance_to_next_point;
+                segments.push(node(at_loc));
+                distance_to_next_point = point_spacing;
+            }
+            distance_to_next_point -= still_to_go;
+        }
+        // Don't leave a sliver of a segment right before the end.
+        if segments
+            .last()
+            .is_some_and(|n: &Node| n.loc.distance(last) < point_spacing * 0.5)
+        {
+            segments.pop();
+        }
+        self.closed = false;
+        self.start = node(first);
+        self.segments = segments;
+        self.end = node(last);
+    }
+
+    /// The nodes that move each step. Endpoints of open rivers stay put.
+    fn moving_nodes(&mut self) -> impl Iterator<Item = &mut Node> {
+        let (start, end) = if self.closed {
+            (Some(&mut self.start), Some(&mut self.end))
+        } else {
+            (None, None)
+        };
+        start.into_iter().chain(&mut self.segments).chain(end)
+    }
+
+    /// Each moving node with its upstream and downstream neighbors.
+    fn moving_neighborhoods(&self) -> impl Iterator<Item = (Node, Node, Node)> {
+        let len = self.segments.len() as isize;
+        let inds = if self.closed { -1..len + 1 } else { 0..len };
+        inds.map(|i| {
+            (
+                self.node(i - 1).unwrap_or(self.start),
+                self.node(i).unwrap(),
+                self.node(i + 1).unwrap_or(self.end),
+            )
+        })
+    }
+
+    /// Records a cut off loop as an oxbow lake, unless it is too short to be a real meander.
+    fn cut_off(&mut self, cutoff: Vec<Node>, min_length: f32) {
+        let loop_length: f32 = cutoff.windows(2).map(|w| w[0].loc.distance(w[1].loc)).sum();
+        if loop_length >= min_length {
+            self.oxbows.push(OxbowLake::new(cutoff, self.time));
+        }
+    }
+
     pub fn distribute(&mut self) {
+        if self.closed {
+            return self.distribute_closed();
+        }
+
         let mut new_nodes = Vec::<Node>::new();
         let mut at_loc = self.start.loc;
         let mut at_ind = 0;
-        let mut distance_to_next_point = POINT_SPACING;
-        let collision_distance = MIN_DISTANCE + 0.1;
-        while at_ind < self.segments.len() {
-            let next_ind = self
-                .segments
-                .iter()
-                .enumerate()
-                .skip(at_ind + 1)
-                .rev()
-                .find_map(|(other_ind, other_node)| {
-                    let ind_diff = at_ind.abs_diff(other_ind);
-                    let close_margin = (MIN_DISTANCE / POINT_SPACING).ceil() as usize * 2;
-                    if ind_diff < close_margin {
-                        None
-                    } else if (other_node.loc - at_loc).length_squared()
-                        < collision_distance * collision_distance
-                    {
-                        Some(other_ind)
-                    } else {
-                        None
-                    }
-                })
+        let SimParams {
+            min_distance,
+            point_spacing,
+            ..
+        } = self.params;
+        let mut distance_to_next_point = point_spacing;
+        let collision_distance = min_distance + 0.1;
+        let close_margin = (min_distance / point_spacing).ceil() as usize * 2;
+        let grid = SpatialGrid::new(collision_distance, self.segments.iter().map(|n| n.loc));
+        let len = self.segments.len();
+        while at_ind < len {
+            let next_ind = grid
+                .farthest_within(
+                    at_loc,
+                    collision_distance,
+                    at_ind + close_margin.max(1)..len,
+                )
                 .unwrap_or(at_ind + 1);
+            if next_ind > at_ind + 1 {
+                let mut cutoff = vec![Node {
+                    loc: at_loc,
+                    ..self.segments[at_ind]
+                }];
+                cutoff.extend_from_slice(&self.segments[at_ind + 1..=next_ind]);
+                // Shortcuts through freshly seeded, tightly packed nodes are not real meanders.
+                self.cut_off(cutoff, close_margin as f32 * point_spacing);
+            }
             let next_node = self.node(next_ind as isize).unwrap_or(self.end);
             let mut line = next_node.loc - at_loc;
             let mut still_to_go = line.length();
@@ -98,13 +249,8 @@ impl River {
                 still_to_go -= step_by;
                 distance_to_next_point -= step_by;
                 if distance_to_next_point <= 0.0 {
-                    new_nodes.push(Node {
-                        loc: at_loc,
-                        tangent: vec2(f32::NAN, f32::NAN),
-                        bitangent: vec2(f32::NAN, f32::NAN),
-                        color: next_node.color,
-                    });
-                    distance_to_next_point = POINT_SPACING;
+                    new_nodes.push(Node::resampled(at_loc, next_node.color));
+                    distance_to_next_point = point_spacing;
                 }
             }
             at_ind = next_ind;
@@ -113,34 +259,192 @@ impl River {
         self.segments = new_nodes;
     }
 
+    /// [`River::distribute`] for rings, where every node is free to move and
+    /// a loop can be cut off anywhere, including across the seam between
+    /// `end` and `start`.
+    fn distribute_closed(&mut self) {
+        let ring: Vec<Node> = self.nodes().copied().collect();
+        let len = ring.len();
+        let SimParams {
+            min_distance,
+            point_spacing,
+            ..
+        } = self.params;
+        let collision_distance = min_distance + 0.1;
+        let close_margin = (min_distance / point_spacing).ceil() as usize * 2;
+        // Indexing two laps lets a walk run past the seam without wrapping.
+        let grid = SpatialGrid::new(collision_distance, ring.iter().chain(&ring).map(|n| n.loc));
+
+        // Walk one lap, always jumping over the shorter side of a collision.
+        // If a jump crosses the seam it also cuts off the start of the lap,
+        // so walk again from where it landed without crossing the seam.
+        let mut begin = 0;
+        let mut path = Vec::new();
+        let mut jumps = Vec::new();
+        for last_try in [false, true] {
+            path.clear();
+            jumps.clear();
+            let mut at_ind = begin;
+            path.push(at_ind);
+            while at_ind < begin + len {
+                let mut inds = at_ind + close_margin.max(1)..at_ind + len / 2 + 1;
+                if last_try {
+                    inds.end = inds.end.min(begin + len + 1);
+                }
+                let next_ind = grid
+                    .farthest_within(ring[at_ind % len].loc, collision_distance, inds)
+                    .unwrap_or(at_ind + 1);
+                if next_ind > at_ind + 1 {
+                    jumps.push(at_ind..=next_ind);
+                }
+                path.push(next_ind);
+                at_ind = next_ind;
+            }
+            if at_ind == begin + len {
+                break;
+            }
+            begin = at_ind - len;
+        }
+        for jump in jumps {
+            let cutoff = jump.map(|i| ring[i % len]).collect();
+            self.cut_off(cutoff, close_margin as f32 * point_spacing);
+        }
+
+        let first = ring[begin % len];
+        let mut new_nodes = vec![first];
+        let mut at_loc = first.loc;
+        let mut distance_to_next_point = point_spacing;
+        for &next_ind in &path[1..] {
+            let next_node = ring[next_ind % len];
+            let mut line = next_node.loc - at_loc;
+            let mut still_to_go = line.length();
+            line /= still_to_go;
+            while still_to_go > 0.01 {
+                let step_by = distance_to_next_point.min(still_to_go);
+                at_loc += line * step_by;
+                still_to_go -= step_by;
+                distance_to_next_point -= step_by;
+                if distance_to_next_point <= 0.0 {
+                    new_nodes.push(Node::resampled(at_loc, next_node.color));
+                    distance_to_next_point = point_spacing;
+                }
+            }
+        }
+        // The walk ends back at `first`, which may have been placed again.
+        if new_nodes
+            .last()
+            .is_some_and(|n| n.loc.distance(first.loc) < point_spacing * 0.5)
+        {
+            new_nodes.pop();
+        }
+        if new_nodes.len() < 3 {
+            return;
+        }
+
+        self.end = new_nodes.pop().unwrap();
+        self.start = new_nodes[0];
+        new_nodes.remove(0);
+        self.segments = new_nodes;
+    }
+
+    /// Pairs of nodes close enough, and far enough apart along the river,
+    /// that [`River::distribute`] would cut off the loop between them. Each
+    /// node is paired with the farthest such node downstream of it.
+    pub fn cutoff_candidates(&self) -> Vec<(Vec2, Vec2)> {
+        let SimParams {
+            min_distance,
+            point_spacing,
+            ..
+        } = self.params;
+        let collision_distance = min_distance + 0.1;
+        let close_margin = (min_distance / point_spacing).ceil() as usize * 2;
+        if self.closed {
+            let ring: Vec<Vec2> = self.nodes().map(|n| n.loc).collect();
+            let len = ring.len();
+            let grid = SpatialGrid::new(collision_distance, ring.iter().chain(&ring).copied());
+            return (0..len)
+                .filter_map(|i| {
+                    let inds = i + close_margin.max(1)..i + len / 2 + 1;
+                    let j = grid.farthest_within(ring[i], collision_distance, inds)?;
+                    Some((ring[i], ring[j % len]))
+                })
+                .collect();
+        }
+
+        // Same indices as the walk in `distribute`, which starts from `start`
+        // in place of the first segment.
+        let len = self.segments.len();
+        let grid = SpatialGrid::new(collision_distance, self.segments.iter().map(|n| n.loc));
+        (0..len)
+            .filter_map(|i| {
+                let at_loc = if i == 0 {
+                    self.start.loc
+                } else {
+                    self.segments[i].loc
+                };
+                let inds = i + close_margin.max(1)..len;
+                let j = grid.farthest_within(at_loc, collision_distance, inds)?;
+                Some((at_loc, self.segments[j].loc))
+            })
+            .collect()
+    }
+
     pub fn recompute(&mut self) {
         for i in 0..self.segments.len() {
-            let (a, b, c) = (
+            let (a, c) = (
                 self.node(i as isize - 1).unwrap_or(self.start),
-                self.segments[i],
                 self.node(i as isize + 1).unwrap_or(self.end),
             );
-            let (tangent, cross) = (
-                (c.loc - a.loc).normalize_or_zero(),
-                (b.loc - a.loc)
-                    .normalize_or_zero()
-                    .perp_dot((c.loc - b.loc).normalize_or_zero()),
-            );
-            self.segments[i].tangent = tangent;
-            self.segments[i].bitangent = (tangent.perp() * cross.signum()).normalize_or_zero();
+            self.segments[i].set_frame(a.loc, c.loc);
+        }
+        if self.closed {
+            let len = self.segments.len() as isize;
+            let (a, c) = (self.node(-2).unwrap(), self.node(0).unwrap());
+            self.start.set_frame(a.loc, c.loc);
+            let (a, c) = (self.node(len - 1).unwrap(), self.node(len + 1).unwrap());
+            self.end.set_frame(a.loc, c.loc);
+        }
+
+        let mut upstream = UpstreamCurvature::new(self.start.loc, self.params.upstream_length);
+        if self.closed {
+            // Warm up with one lap so the start of the ring sees the curvature behind it.
+            upstream.prev = self.end.loc;
+            for node in self.nodes() {
+                upstream.push(node);
+            }
+        }
+        for node in self.moving_nodes() {
+            node.upstream_curvature = upstream.push(node);
         }
     }
 
-    pub fn step(&mut self, update: Update, heightmap: &Heightmap) {
-        for node in &mut self.segments {
-            node.step(update, heightmap);
+    pub fn step(&mut self, dt: f32, heightmap: &dyn Terrain, widthmap: &dyn Terrain) {
+        let env = Environment {
+            heightmap,
+            widthmap,
+            params: &self.params,
+            time: self.time,
+            dt: (dt - self.params.slowdown) * self.params.speed,
+        };
+        let moves: Vec<Vec2> = self
+            .moving_neighborhoods()
+            .map(|(prev, node, next)| {
+                self.forces
+                    .iter()
+                    .map(|force| force.displacement(&prev, &node, &next, &env))
+                    .fold(Vec2::ZERO, |total, moved| total + moved)
+            })
+            .collect();
+        for (node, moved) in self.moving_nodes().zip(moves) {
+            node.loc += moved;
         }
+        self.time += dt as f64;
     }
 
-    pub fn tesselate(&mut self, widthmap: &Heightmap) {
+    pub fn tesselate(&mut self, widthmap: &dyn Terrain) {
         self.river_builder.abort_geometry();
 
-        let getwidth = |p| widthmap.get(p) * 10.0 + 15.0;
+        let getwidth = |p| self.params.width_at(widthmap, p);
         let mut path_builder = lyon::path::Path::builder_with_attributes(5);
         {
             let (p, a) = self.start.lyonize(getwidth(self.start.loc));
@@ -165,63 +469,127 @@ impl River {
                 .tessellate_path(&path, &opts, &mut self.river_builder)
                 .unwrap();
         }
+
+        let (time, params) = (self.time, self.params);
+        self.oxbows
+            .retain_mut(|lake| lake.tesselate(time, widthmap, &params));
     }
 
-    pub fn draw_fill(&self, draw: &Draw) {
-        draw.mesh()
-            .indexed_colored(
-                self.river_builder.vertices.iter().copied(),
-                self.river_builder.indicies.iter().copied(),
-            )
-            .finish();
+    /// Left and right bank polylines from the last [`River::tesselate`],
+    /// ending where they began when the river is closed.
+    pub fn banks(&self) -> [Vec<Vec2>; 2] {
+        [
+            self.river_builder.left_bank(),
+            self.river_builder.right_bank(),
+        ]
+        .map(|bank| {
+            let mut points: Vec<Vec2> = bank.iter().map(|p| p.1).collect();
+            if self.closed
+                && let Some(&first) = points.first()
+            {
+                points.push(first);
+            }
+            points
+        })
     }
+}
 
-    pub fn draw_for_history(&self, draw: &Draw) {
-        draw.mesh()
-            .indexed(
-                self.river_builder.vertices.iter().map(|p| p.0),
-                self.river_builder.indicies.iter().copied(),
-            )
-            .rgba(0.0, 0.0, 0.0, 1.0)
-            .finish();
-        let line = draw.polyline().weight(1.0).rgba(0.0, 1.0, 0.0, 1.0);
-        if self.closed {
-            line.points(self.river_builder.left_bank.iter().map(|p| p.1));
-        } else {
-            line.points(self.river_builder.left_bank.iter().map(|p| p.1));
+/// Running exponentially weighted average of curvature along the river.
+struct UpstreamCurvature {
+    prev: Vec2,
+    decay_length: f32,
+    sum: f32,
+    weight: f32,
+}
+
+impl UpstreamCurvature {
+    fn new(prev: Vec2, decay_length: f32) -> Self {
+        UpstreamCurvature {
+            prev,
+            decay_length,
+            sum: 0.0,
+            weight: 0.0,
         }
-        let line = draw.polyline().weight(1.0).rgba(0.0, 1.0, 0.0, 1.0);
-        if self.closed {
-            line.points(self.river_builder.right_bank.iter().map(|p| p.1));
+    }
+
+    /// Advances to `node` and returns the average curvature upstream of it.
+    fn push(&mut self, node: &Node) -> f32 {
+        let ds = node.loc.distance(self.prev);
+        self.prev = node.loc;
+        let decay = (-ds / self.decay_length).exp();
+        self.sum = self.sum * decay + node.curvature * ds;
+        self.weight = self.weight * decay + ds;
+        if self.weight > 0.0 {
+            self.sum / self.weight
         } else {
-            line.points(self.river_builder.right_bank.iter().map(|p| p.1));
+            0.0
         }
     }
+}
 
-    pub fn draw_border(&self, draw: &Draw) {
-        let line = draw.polyline().weight(2.0).color(BLACK);
-        if self.closed {
-            line.points(self.river_builder.left_bank.iter().map(|p| p.1));
-        } else {
-            line.points(self.river_builder.left_bank.iter().map(|p| p.1));
+#[cfg(feature = "gui")]
+impl River {
+    pub fn draw_oxbow_fill(&self, draw: &Draw) {
+        for lake in &self.oxbows {
+            lake.draw_fill(draw);
         }
-        let line = draw.polyline().weight(2.0).color(BLACK);
-        if self.closed {
-            line.points(self.river_builder.right_bank.iter().map(|p| p.1));
-        } else {
-            line.points(self.river_builder.right_bank.iter().map(|p| p.1));
+    }
+
+    pub fn draw_oxbow_border(&self, draw: &Draw, ink: LinSrgba) {
+        for lake in &self.oxbows {
+            lake.draw_border(draw, ink);
+        }
+    }
+
+    pub fn draw_fill(&self, draw: &Draw) {
+        draw.mesh()
+            .indexed_colored(
+                self.river_builder
+                    .vertices
+                    .iter()
+                    .map(|&(p, c)| (p, lin_srgba(c.x, c.y, c.z, c.w))),
+                self.river_builder.indicies.iter().copied(),
+            )
+            .finish();
+    }
+
+    pub fn draw_border(&self, draw: &Draw, ink: LinSrgba) {
+        for bank in self.banks() {
+            draw.polyline().weight(2.0).color(ink).points(bank);
         }
     }
 }
 
 #[derive(Debug, Default, Clone)]
 pub struct RiverMeshBuilder {
-    vertices: Vec<(Vec3, LinSrgba)>,
+    vertices: Vec<(Vec3, Vec4)>,
     indicies: Vec<usize>,
     left_bank: Vec<(f32, Vec2)>,
     right_bank: Vec<(f32, Vec2)>,
 }
 
+impl RiverMeshBuilder {
+    /// Fill vertices with their interpolated linear RGBA color.
+    pub fn vertices(&self) -> &[(Vec3, Vec4)] {
+        &self.vertices
+    }
+
+    /// Triangle list indexing into [`RiverMeshBuilder::vertices`].
+    pub fn indices(&self) -> &[usize] {
+        &self.indicies
+    }
+
+    /// Left bank polyline as `(advancement, position)` pairs, sorted by advancement.
+    pub fn left_bank(&self) -> &[(f32, Vec2)] {
+        &self.left_bank
+    }
+
+    /// Right bank polyline as `(advancement, position)` pairs, sorted by advancement.
+    pub fn right_bank(&self) -> &[(f32, Vec2)] {
+        &self.right_bank
+    }
+}
+
 impl tes::GeometryBuilder for RiverMeshBuilder {
     fn add_triangle(&mut self, a: tes::VertexId, b: tes::VertexId, c: tes::VertexId) {
         self.indicies.push(a.to_usize());
@@ -265,7 +633,122 @@ impl tes::StrokeGeometryBuilder for RiverMeshBuilder {
         let i = self.vertices.len() as u32;
         let p = vec3(vertex.position().x, vertex.position().y, 0.0);
         let a = vertex.interpolated_attributes();
-        self.vertices.push((p, lin_srgba(a[1], a[2], a[3], a[4])));
+        self.vertices.push((p, vec4(a[1], a[2], a[3], a[4])));
         Ok(tes::VertexId(i))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::TAU;
+
+    const RADIUS: f32 = 100.0;
+    /// Gap between the two sides of the pinch, well inside `min_distance`.
+    const NECK: f32 = 6.0;
+
+    /// A circle with a narrow-necked lobe sticking out to the right,
+    /// counterclockwise from the lobe's tip.
+    fn pinched_ring() -> Vec<Vec2> {
+        let h = NECK / 2.0;
+        let x0 = (RADIUS * RADIUS - h * h).sqrt();
+        let (length, width) = (40.0, 20.0);
+        let a = (h / RADIUS).asin();
+        let mut corners = vec![vec2(x0 + length + width, 0.0), vec2(x0 + length, h + width)];
+        corners.extend((0..=200).map(|i| {
+            let theta = a + (TAU - 2.0 * a) * i as f32 / 200.0;
+            vec2(theta.cos(), theta.sin()) * RADIUS
+        }));
+        corners.push(vec2(x0 + length, -h - width));
+        resample_loop(&corners, SimParams::default().point_spacing)
+    }
+
+    /// Points every `spacing` along the closed polyline through `corners`.
+    fn resample_loop(corners: &[Vec2], spacing: f32) -> Vec<Vec2> {
+        let mut points = vec![corners[0]];
+        let mut to_next = spacing;
+        for (&a, &b) in corners.iter().zip(corners.iter().cycle().skip(1)) {
+            let (dir, mut left) = ((b - a).normalize(), a.distance(b));
+            let mut at = a;
+            while left >= to_next {
+                at += dir * to_next;
+                left -= to_next;
+                to_next = spacing;
+                points.push(at);
+            }
+            to_next -= left;
+        }
+        if points.last().unwrap().distance(corners[0]) < spacing * 0.5 {
+            points.pop();
+        }
+        points
+    }
+
+    fn river(points: &[Vec2], closed: bool) -> River {
+        let node = |loc| Node {
+            loc,
+            ..Default::default()
+        };
+        let (&first, rest) = points.split_first().unwrap();
+        let (&last, middle) = rest.split_last().unwrap();
+        River {
+            closed,
+            start: node(first),
+            segments: middle.iter().copied().map(node).collect(),
+            end: node(last),
+            ..River::default()
+        }
+    }
+
+    #[test]
+    fn pinch_across_the_seam_is_cut_once() {
+        let points = pinched_ring();
+        let tip = points[0];
+        let mut river = river(&points, true);
+        river.distribute();
+
+        assert!(river.closed);
+        assert_eq!(river.oxbows.len(), 1);
+        let lake = &river.oxbows[0].nodes;
+        assert!(lake.iter().any(|n| n.loc == tip));
+        // The lobe is gone from the river itself.
+        assert!(river.nodes().all(|n| n.loc.x < RADIUS + 5.0));
+    }
+
+    #[test]
+    fn closed_walk_never_duplicates_the_seam() {
+        let mut river = river(&pinched_ring(), true);
+        river.distribute();
+
+        let spacing = river.params.point_spacing;
+        let ring: Vec<Vec2> = river.nodes().map(|n| n.loc).collect();
+        assert!(ring.len() > 3);
+        for (a, b) in ring.iter().zip(ring.iter().cycle().skip(1)) {
+            assert!(a.distance(*b) > spacing * 0.5, "{a} and {b} are doubled");
+        }
+    }
+
+    #[test]
+    fn pinch_inside_the_lap_is_cut_like_an_open_river() {
+        // Start opposite the lobe, so the cutoff never nears the seam.
+        let mut points = pinched_ring();
+        let far_side = (points.iter().enumerate())
+            .min_by(|(_, a), (_, b)| a.x.total_cmp(&b.x))
+            .unwrap()
+            .0;
+        points.rotate_left(far_side);
+        let mut closed = river(&points, true);
+        // Leave a gap at the seam so the open river's ends don't touch.
+        let mut open = river(&points[5..points.len() - 5], false);
+        closed.distribute();
+        open.distribute();
+
+        assert_eq!(closed.oxbows.len(), 1);
+        assert_eq!(open.oxbows.len(), 1);
+        let (closed_lake, open_lake) = (&closed.oxbows[0].nodes, &open.oxbows[0].nodes);
+        assert_eq!(closed_lake.len(), open_lake.len());
+        for (a, b) in closed_lake.iter().zip(open_lake) {
+            assert!(a.loc.distance(b.loc) < 1e-3, "{} vs {}", a.loc, b.loc);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use crate::simulation::Simulation;
+use crate::{Node, OxbowLake, SimParams};
+use glam::{Vec2, Vec4};
+use serde::{Deserialize, Serialize};
+use std::io;
+
+/// Bumped whenever [`SavedState`] changes shape. Older files are rejected
+/// rather than silently misread.
+pub const SAVE_VERSION: u32 = 1;
+
+/// Everything needed to resume a [`Simulation`] bit for bit.
+///
+/// Only node locations and colors are kept: the tangent frames and
+/// curvatures are rebuilt by [`River::recompute`](crate::River::recompute)
+/// at the start of every step, and the meshes by
+/// [`River::tesselate`](crate::River::tesselate).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedState {
+    pub version: u32,
+    pub seed: u64,
+    pub height_seed: u32,
+    pub width_seed: u32,
+    pub dt: f32,
+    pub steps: u64,
+    pub time: f64,
+    pub params: SimParams,
+    pub closed: bool,
+    pub start: SavedNode,
+    pub segments: Vec<SavedNode>,
+    pub end: SavedNode,
+    pub oxbows: Vec<SavedOxbow>,
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct SavedNode {
+    pub loc: [f32; 2],
+    /// Linear RGBA.
+    pub color: [f32; 4],
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedOxbow {
+    pub created_at: f64,
+    pub nodes: Vec<SavedNode>,
+}
+
+impl SavedState {
+    pub fn new(sim: &Simulation) -> Self {
+        let river = &sim.river;
+        SavedState {
+            version: SAVE_VERSION,
+            seed: sim.seed,
+            height_seed: sim.height_seed,
+            width_seed: sim.width_seed,
+            dt: sim.dt,
+            steps: sim.steps,
+            time: river.time,
+            params: river.params,
+            closed: river.closed,
+            start: (&river.start).into(),
+            segments: river.segments.iter().map(SavedNode::from).collect(),
+            end: (&river.end).into(),
+            oxbows: river.oxbows.iter().map(SavedOxbow::from).collect(),
+        }
+    }
+
+    pub fn restore(self) -> io::Result<Simulation> {
+        if self.version != SAVE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "save file version {} is not supported, expected {SAVE_VERSION}",
+                    self.version
+                ),
+            ));
+        }
+        let mut sim = Simulation::with_seeds(
+            self.seed,
+            self.height_seed,
+            self.width_seed,
+            self.dt,
+            self.params,
+        );
+        sim.steps = self.steps;
+        let river = &mut sim.river;
+        river.time = self.time;
+        river.closed = self.closed;
+        river.start = self.start.into();
+        river.segments = self.segments.into_iter().map(Node::from).collect();
+        river.end = self.end.into();
+        river.oxbows = self.oxbows.into_iter().map(OxbowLake::from).collect();
+        river.recompute();
+        river.tesselate(&*sim.widthmap);
+        Ok(sim)
+    }
+}
+
+impl From<&Node> for SavedNode {
+    fn from(node: &Node) -> Self {
+        SavedNode {
+            loc: node.loc.to_array(),
+            color: node.color.to_array(),
+        }
+    }
+}
+
+impl From<SavedNode> for Node {
+    fn from(node: SavedNode) -> Self {
+        Node {
+            loc: Vec2::from(node.loc),
+            color: Vec4::from(node.color),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<&OxbowLake> for SavedOxbow {
+    fn from(lake: &OxbowLake) -> Self {
+        SavedOxbow {
+            created_at: lake.created_at,
+            nodes: lake.nodes.iter().map(SavedNode::from).collect(),
+        }
+    }
+}
+
+impl From<SavedOxbow> for OxbowLake {
+    fn from(lake: SavedOxbow) -> Self {
+        let nodes = lake.nodes.into_iter().map(Node::from).collect();
+        OxbowLake::new(nodes, lake.created_at)
+    }
+}
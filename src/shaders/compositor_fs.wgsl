@@ -2,25 +2,87 @@ struct FragmentOutput {
     @location(0) f_color: vec4<f32>,
 };
 
+// Sim time the river last covered each pixel in r, and whether that was
+// under a bank line in g.
 @group(0) @binding(0)
-var history_tex: texture_multisampled_2d<f32>;
+var history_tex: texture_2d<f32>;
 @group(0) @binding(1)
 var border_tex: texture_multisampled_2d<f32>;
 @group(0) @binding(2)
 var fill_tex: texture_multisampled_2d<f32>;
 
+// Mirrors `HistoryUniform` in compositor.rs.
+struct HistoryStyle {
+    // OKLCH in xyz.
+    palette: array<vec4<f32>, 8>,
+    // Sixteen `HatchPattern`s, four to a vector.
+    patterns: array<vec4<u32>, 4>,
+    num_stops: u32,
+    num_patterns: u32,
+    max_age: f32,
+    pattern_scale: f32,
+};
+
+@group(0) @binding(3)
+var<uniform> history_style: HistoryStyle;
+
+// Mirrors `PaperUniform` in compositor.rs.
+struct PaperStyle {
+    // OKLCH in xyz.
+    color: vec4<f32>,
+    dark: vec4<f32>,
+    ink: vec4<f32>,
+    folds: vec2<f32>,
+    blotch_scale: f32,
+    grain_scale: f32,
+    blotch_strength: f32,
+    grain_strength: f32,
+    fold_sharpness: f32,
+    vignette: f32,
+    stains: u32,
+    plain: u32,
+};
+
+@group(0) @binding(4)
+var<uniform> paper_style: PaperStyle;
+
+// Mirrors `ViewUniform` in compositor.rs.
+struct View {
+    // Window size over map size, both in points.
+    history_extent: vec2<f32>,
+    // Current sim time.
+    time: f32,
+    // Sim seconds per snapshot, the unit history ages are styled in.
+    snapshot_every: f32,
+};
+
+// Mirrors `NEVER_OCCUPIED` in shading.rs.
+const NEVER_OCCUPIED: f32 = -1.0e30;
+
+@group(0) @binding(5)
+var<uniform> view: View;
+
 @fragment
 fn main(
     @location(0) tex_coords: vec2<f32>,
     @builtin(sample_index) sample_index: u32,
 ) -> FragmentOutput {
-    let tex_size: vec2<u32> = textureDimensions(history_tex);
+    let tex_size: vec2<u32> = textureDimensions(border_tex);
     let tex_x: i32 = i32(f32(tex_size.x) * tex_coords.x);
     let tex_y: i32 = i32(f32(tex_size.y) * tex_coords.y);
     let itex_coords: vec2<i32> = vec2<i32>(tex_x, tex_y);
 
-    var history: vec4<f32> = textureLoad(history_tex, itex_coords, i32(sample_index));
-    history = history_color(tex_coords, history);
+    // The history covers the map rather than the window.
+    let history_coords = 0.5 + (tex_coords - 0.5) * view.history_extent;
+    var lookup = vec2(NEVER_OCCUPIED, 0.0);
+    if all(history_coords >= vec2(0.0)) && all(history_coords < vec2(1.0)) {
+        let history_size = vec2<f32>(textureDimensions(history_tex));
+        let ihistory_coords = vec2<i32>(history_coords * history_size);
+        lookup = textureLoad(history_tex, ihistory_coords, 0).rg;
+    }
+    // Stamps from after `time` are left over from rewinding.
+    let age = max(view.time - lookup.r, 0.0) / view.snapshot_every;
+    let history = history_color(tex_coords, age, lookup.g);
     let paper = paper(tex_coords);
     let fill: vec4<f32> = paper * textureLoad(fill_tex, itex_coords, i32(sample_index)).a;
     let border: vec4<f32> = textureLoad(border_tex, itex_coords, i32(sample_index));
@@ -28,21 +90,42 @@ fn main(
 }
 
 fn paper(loc: vec2<f32>) -> vec4<f32> {
-    let color = vec3(0.8386, 0.052, 84.51);
-    let darkest = vec3(0.7920, 0.057, 85.00);
-    let large = (keep_over(simplex2d(loc * 10.0), 0.5) - 0.5) * 0.5;
-    let many = min(normal_range(simplex2d(loc * 100.0)), normal_range(simplex2d(loc * 100.0 + 100.0))) * 0.7;
+    let color = paper_style.color.xyz;
+    if paper_style.plain != 0u {
+        return vec4(oklch_to_lin(color), 1.0);
+    }
+    let darkest = paper_style.dark.xyz;
+    let large = (keep_over(simplex2d(loc * paper_style.blotch_scale), 0.5) - 0.5) * paper_style.blotch_strength;
+    let many = min(normal_range(simplex2d(loc * paper_style.grain_scale)), normal_range(simplex2d(loc * paper_style.grain_scale + 100.0))) * paper_style.grain_strength;
     let darken = clamp(large + many, 0.0, 1.0);
-    let folded = max(fold(loc.x, 4.0), fold(loc.y, 3.0));
-    let shifted = mix(color, darkest, min(folded, 1.0) + darken);
+    let folded = max(fold(loc.x, paper_style.folds.x), fold(loc.y, paper_style.folds.y));
+    let stained = stains(loc, min(paper_style.stains, 8u));
+    let vignette = smoothstep(0.6, 1.5, length(loc - 0.5) * 2.0) * paper_style.vignette;
+    let shifted = mix(color, darkest, min(folded, 1.0) + darken + stained + vignette);
 
     return vec4(oklch_to_lin(shifted), 1.0);
 }
 
 fn fold(loc: f32, num_folds: f32) -> f32 {
+    if num_folds <= 0.0 {
+        return 0.0;
+    }
     let dist = abs(fract(loc * num_folds) - 0.5) * 2.0;
-    let lines = pow(20000000.0, dist) / 20000000.0;
-    return lines * 1.0;
+    return exp(paper_style.fold_sharpness * (dist - 1.0));
+}
+
+fn stains(loc: vec2<f32>, count: u32) -> f32 {
+    var darken = 0.0;
+    for (var i = 0u; i < count; i++) {
+        let seed = vec2(f32(i), 17.0);
+        let center = hash(seed) * 0.4 + 0.5;
+        let radius = 0.05 + 0.04 * (hash(seed + 3.0).x + 1.0);
+        let from_rim = (length(loc - center) - radius) / 0.006;
+        let ring = exp(-from_rim * from_rim) * 0.8;
+        let inside = select(0.0, 0.15, from_rim < 0.0);
+        darken += ring + inside;
+    }
+    return darken;
 }
 
 fn keep_over(keep: f32, over: f32) -> f32 {
@@ -76,32 +159,32 @@ fn simplex2d(p: vec2<f32>) -> f32 {
     return dot(n, vec3(70.0));
 }
 
-fn history_color(tex_coords: vec2<f32>, lookup: vec4<f32>) -> vec4<f32> {
-    let age = lookup.r * 255.0;
-    // let offset = tex_coords + age * vec2(0.318374, 0.73492);
-    let offset = tex_coords;
-    let is_border = lookup.g;
-    let color = gradient(age / 20.0);
-    let pattern = u32(age) % 6u;
-    if age > 20.0 {
+fn history_color(tex_coords: vec2<f32>, age: f32, is_border: f32) -> vec4<f32> {
+    if age > history_style.max_age {
         return vec4(0.0, 0.0, 0.0, 0.0);
     }
+    // let offset = tex_coords + age * vec2(0.318374, 0.73492);
+    let offset = tex_coords;
+    let color = gradient(age / history_style.max_age);
+    let index = u32(age) % history_style.num_patterns;
+    let pattern = history_style.patterns[index / 4u][index % 4u];
+    let size = history_style.pattern_scale;
     if is_border > 0.99 {
-        return vec4(mix(color, vec3(0.0, 0.0, 0.0), 0.5), 1.0);
+        return vec4(mix(color, oklch_to_lin(paper_style.ink.xyz), 0.5), 1.0);
     }
-    if pattern < 2u {
+    if pattern == SOLID {
         return vec4(color, 1.0);
-    } else if pattern < 4u {
-        let hex_center = pointy_hex_to_pixel(round(pixel_to_pointy_hex(offset)));
+    } else if pattern == HEX_HOLES || pattern == HEX_DOTS {
+        let hex_center = pointy_hex_to_pixel(round(pixel_to_pointy_hex(offset, size)), size);
         let dist = length(offset - hex_center);
-        if (dist < (0.5 * size)) != (pattern == 3u) {
+        if (dist < (0.5 * size)) != (pattern == HEX_DOTS) {
             return vec4(0.0, 0.0, 0.0, 0.0);
         } else {
             return vec4(color, 1.0);
         }
     } else {
         let t = fract((offset.x - offset.y) / (size * 2.0 * sqrt(2.0)));
-        if (t < 0.5) != (pattern == 5u) {
+        if (t < 0.5) != (pattern == OFFSET_STRIPES) {
             return vec4(0.0, 0.0, 0.0, 0.0);
         } else {
             return vec4(color, 1.0);
@@ -109,9 +192,14 @@ fn history_color(tex_coords: vec2<f32>, lookup: vec4<f32>) -> vec4<f32> {
     }
 }
 
-const size: f32 = 0.01;
+// `HatchPattern` discriminants.
+const SOLID: u32 = 0u;
+const HEX_HOLES: u32 = 1u;
+const HEX_DOTS: u32 = 2u;
+const STRIPES: u32 = 3u;
+const OFFSET_STRIPES: u32 = 4u;
 
-fn pixel_to_pointy_hex(point: vec2<f32>) -> vec2<f32> {
+fn pixel_to_pointy_hex(point: vec2<f32>, size: f32) -> vec2<f32> {
     // invert the scaling
     let x = point.x / size;
     let y = point.y / size;
@@ -121,7 +209,7 @@ fn pixel_to_pointy_hex(point: vec2<f32>) -> vec2<f32> {
     return vec2(q, r);
 }
 
-fn pointy_hex_to_pixel(hex: vec2<f32>) -> vec2<f32> {
+fn pointy_hex_to_pixel(hex: vec2<f32>, size: f32) -> vec2<f32> {
     // hex to cartesian
     var x = (sqrt(3.0) * hex.x + sqrt(3.0) / 2.0 * hex.y);
     var y = (3.0 / 2.0 * hex.y);
@@ -177,20 +265,12 @@ fn oklch_to_lin(oklch: vec3<f32>) -> vec3<f32> {
     // );
 }
 
-const num_grad_stops: u32 = 4u;
-
 fn gradient(pos: f32) -> vec3<f32> {
-    var grad_stops = array<vec3<f32>, num_grad_stops>(
-        vec3(0.7, 0.1135, 48.18),
-        vec3(0.7, 0.1135, 130.41),
-        vec3(0.7, 0.1135, 140.41),
-        vec3(0.7, 0.1135, 239.82),
-    );
-
+    let num_grad_stops = history_style.num_stops;
     let pos_mul = clamp(pos, 0.0, 1.0) * f32(num_grad_stops);
     let t = fract(pos_mul);
-    let i = u32(pos_mul);
+    let i = min(u32(pos_mul), num_grad_stops - 1u);
     let j = min(i + 1u, num_grad_stops - 1u);
-    let color = mix(grad_stops[i], grad_stops[j], t);
+    let color = mix(history_style.palette[i].xyz, history_style.palette[j].xyz, t);
     return oklch_to_lin(color);
 }
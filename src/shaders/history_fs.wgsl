@@ -0,0 +1,9 @@
+struct FragmentOutput {
+    @location(0) f_stamp: vec4<f32>,
+};
+
+// The stamp is the sim time in x and 1.0 for bank lines in y, written as is.
+@fragment
+fn main(@location(0) stamp: vec2<f32>) -> FragmentOutput {
+    return FragmentOutput(vec4<f32>(stamp, 0.0, 1.0));
+}
@@ -0,0 +1,15 @@
+struct FragmentOutput {
+    @location(0) f_stamp: vec4<f32>,
+};
+
+// The history being replaced, at the old scale factor.
+@group(0) @binding(0)
+var old_history: texture_2d<f32>;
+
+// Nearest neighbour, as stamps can't be blended.
+@fragment
+fn main(@location(0) tex_coords: vec2<f32>) -> FragmentOutput {
+    let old_size = textureDimensions(old_history);
+    let coords = min(vec2<u32>(tex_coords * vec2<f32>(old_size)), old_size - 1u);
+    return FragmentOutput(textureLoad(old_history, coords, 0));
+}
@@ -0,0 +1,12 @@
+struct VertexOutput {
+    @location(0) tex_coords: vec2<f32>,
+    @builtin(position) out_pos: vec4<f32>,
+};
+
+// One triangle covering the whole target, with no vertex buffer.
+@vertex
+fn main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let corner = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    let tex_coords = vec2<f32>(corner.x, 1.0 - corner.y);
+    return VertexOutput(tex_coords, vec4<f32>(corner * 2.0 - 1.0, 0.0, 1.0));
+}
@@ -0,0 +1,9 @@
+struct VertexOutput {
+    @location(0) stamp: vec2<f32>,
+    @builtin(position) out_pos: vec4<f32>,
+};
+
+@vertex
+fn main(@location(0) pos: vec2<f32>, @location(1) stamp: vec2<f32>) -> VertexOutput {
+    return VertexOutput(stamp, vec4<f32>(pos, 0.0, 1.0));
+}
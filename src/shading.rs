@@ -0,0 +1,210 @@
+//! CPU port of `compositor_fs.wgsl`, kept line for line so headless renders
+//! match the viewer.
+
+// Constants are copied verbatim from the shader.
+#![allow(clippy::excessive_precision)]
+
+use crate::style::{HatchPattern, HistoryStyle, PaperStyle};
+use glam::{Vec2, Vec3, Vec4, vec2, vec3};
+
+pub fn paper(loc: Vec2, style: &PaperStyle) -> Vec4 {
+    let color = Vec3::from(style.color);
+    if style.plain {
+        return oklch_to_lin(color).extend(1.0);
+    }
+    let darkest = Vec3::from(style.dark);
+    let large = (keep_over(simplex2d(loc * style.blotch_scale), 0.5) - 0.5) * style.blotch_strength;
+    let many = normal_range(simplex2d(loc * style.grain_scale))
+        .min(normal_range(simplex2d(loc * style.grain_scale + 100.0)))
+        * style.grain_strength;
+    let darken = (large + many).clamp(0.0, 1.0);
+    let folded = fold(loc.x, style.folds[0], style.fold_sharpness).max(fold(
+        loc.y,
+        style.folds[1],
+        style.fold_sharpness,
+    ));
+    let stained = stains(loc, style.stains.min(PaperStyle::MAX_STAINS));
+    let vignette = smoothstep(0.6, 1.5, (loc - 0.5).length() * 2.0) * style.vignette;
+    let shifted = color.lerp(darkest, folded.min(1.0) + darken + stained + vignette);
+
+    oklch_to_lin(shifted).extend(1.0)
+}
+
+fn fold(loc: f32, num_folds: f32, sharpness: f32) -> f32 {
+    if num_folds <= 0.0 {
+        return 0.0;
+    }
+    let dist = (fract(loc * num_folds) - 0.5).abs() * 2.0;
+    (sharpness * (dist - 1.0)).exp()
+}
+
+/// Darkening from `count` coffee rings placed by [`hash`].
+fn stains(loc: Vec2, count: u32) -> f32 {
+    let mut darken = 0.0;
+    for i in 0..count {
+        let seed = vec2(i as f32, 17.0);
+        let center = hash(seed) * 0.4 + 0.5;
+        let radius = 0.05 + 0.04 * (hash(seed + 3.0).x + 1.0);
+        let from_rim = ((loc - center).length() - radius) / 0.006;
+        let ring = (-from_rim * from_rim).exp() * 0.8;
+        let inside = if from_rim < 0.0 { 0.15 } else { 0.0 };
+        darken += ring + inside;
+    }
+    darken
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn keep_over(keep: f32, over: f32) -> f32 {
+    if keep >= over { keep } else { 0.0 }
+}
+
+fn normal_range(x: f32) -> f32 {
+    (x + 1.0) / 2.0
+}
+
+/// WGSL `fract`, which unlike [`f32::fract`] is always positive.
+fn fract(x: f32) -> f32 {
+    x - x.floor()
+}
+
+// Next two copied from https://www.shadertoy.com/view/Msf3WH
+fn hash(p: Vec2) -> Vec2 {
+    let p2 = vec2(p.dot(vec2(127.1, 311.7)), p.dot(vec2(269.5, 183.3)));
+    let s = vec2(p2.x.sin(), p2.y.sin()) * 43758.5453123;
+    vec2(fract(s.x), fract(s.y)) * 2.0 - 1.0
+}
+
+fn simplex2d(p: Vec2) -> f32 {
+    const K1: f32 = 0.366025404; // (sqrt(3)-1)/2;
+    const K2: f32 = 0.211324865; // (3-sqrt(3))/6;
+    let i = (p + (p.x + p.y) * K1).floor();
+    let a = p - i + (i.x + i.y) * K2;
+    let o = vec2(
+        if a.x >= a.y { 1.0 } else { 0.0 },
+        if a.y >= a.x { 1.0 } else { 0.0 },
+    );
+    let b = a - o + K2;
+    let c = a - 1.0 + 2.0 * K2;
+    let h = (Vec3::splat(0.5) - vec3(a.dot(a), b.dot(b), c.dot(c))).max(Vec3::ZERO);
+    let n = h * h * h * h * vec3(a.dot(hash(i)), b.dot(hash(i + o)), c.dot(hash(i + 1.0)));
+    n.dot(Vec3::splat(70.0))
+}
+
+/// What the history holds where the river has never been. Far enough in
+/// the past to always be older than `max_age`, but still finite.
+pub const NEVER_OCCUPIED: f32 = -1.0e30;
+
+/// Color of the floodplain history at `tex_coords`, `age` snapshots after
+/// the river last covered it. `is_border` marks old bank lines, which are
+/// shaded toward the linear RGB `ink`.
+pub fn history_color(
+    tex_coords: Vec2,
+    age: f32,
+    is_border: f32,
+    ink: Vec3,
+    style: &HistoryStyle,
+) -> Vec4 {
+    if age > style.max_age {
+        return Vec4::ZERO;
+    }
+    let offset = tex_coords;
+    let color = gradient(style.palette(), age / style.max_age);
+    let patterns = style.patterns();
+    let pattern = patterns[age as usize % patterns.len()];
+    let size = style.pattern_scale;
+    if is_border > 0.99 {
+        return color.lerp(ink, 0.5).extend(1.0);
+    }
+    match pattern {
+        HatchPattern::Solid => color.extend(1.0),
+        HatchPattern::HexHoles | HatchPattern::HexDots => {
+            let hex = pixel_to_pointy_hex(offset, size);
+            let hex_center =
+                pointy_hex_to_pixel(vec2(hex.x.round_ties_even(), hex.y.round_ties_even()), size);
+            let dist = (offset - hex_center).length();
+            if (dist < 0.5 * size) != (pattern == HatchPattern::HexDots) {
+                Vec4::ZERO
+            } else {
+                color.extend(1.0)
+            }
+        }
+        HatchPattern::Stripes | HatchPattern::OffsetStripes => {
+            let t = fract((offset.x - offset.y) / (size * 2.0 * 2.0f32.sqrt()));
+            if (t < 0.5) != (pattern == HatchPattern::OffsetStripes) {
+                Vec4::ZERO
+            } else {
+                color.extend(1.0)
+            }
+        }
+    }
+}
+
+fn pixel_to_pointy_hex(point: Vec2, size: f32) -> Vec2 {
+    // invert the scaling
+    let x = point.x / size;
+    let y = point.y / size;
+    // cartesian to hex
+    let q = 3.0f32.sqrt() / 3.0 * x - 1.0 / 3.0 * y;
+    let r = 2.0 / 3.0 * y;
+    vec2(q, r)
+}
+
+fn pointy_hex_to_pixel(hex: Vec2, size: f32) -> Vec2 {
+    // hex to cartesian
+    let x = 3.0f32.sqrt() * hex.x + 3.0f32.sqrt() / 2.0 * hex.y;
+    let y = 3.0 / 2.0 * hex.y;
+    // scale cartesian coordinates
+    vec2(x * size, y * size)
+}
+
+pub fn alpha_over(fg: Vec4, bg: Vec4) -> Vec4 {
+    let out_a = fg.w + bg.w * (1.0 - fg.w);
+    let out_rgb = (fg.truncate() * fg.w + bg.truncate() * bg.w * (1.0 - fg.w)) / out_a;
+    out_rgb.extend(out_a)
+}
+
+pub fn oklch_to_lin(oklch: Vec3) -> Vec3 {
+    let (l, c, h) = (oklch.x, oklch.y, oklch.z.to_radians());
+
+    // OKLCH -> OKLab
+    let a = c * h.cos();
+    let b = c * h.sin();
+
+    // OKLab -> linear sRGB
+    let l_ = l + 0.3963377774 * a + 0.2158037573 * b;
+    let m_ = l - 0.1055613458 * a - 0.0638541728 * b;
+    let s_ = l - 0.0894841775 * a - 1.2914855480 * b;
+
+    let l = l_ * l_ * l_;
+    let m = m_ * m_ * m_;
+    let s = s_ * s_ * s_;
+
+    vec3(
+        4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s,
+        -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s,
+        -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s,
+    )
+}
+
+fn gradient(stops: &[[f32; 3]], pos: f32) -> Vec3 {
+    let num_grad_stops = stops.len();
+    let pos_mul = pos.clamp(0.0, 1.0) * num_grad_stops as f32;
+    let t = fract(pos_mul);
+    let i = (pos_mul as usize).min(num_grad_stops - 1);
+    let j = (i + 1).min(num_grad_stops - 1);
+    let color = Vec3::from(stops[i]).lerp(Vec3::from(stops[j]), t);
+    oklch_to_lin(color)
+}
+
+/// Linear to sRGB transfer function.
+pub fn gamma_correct(c: f32) -> f32 {
+    if c > 0.0031308 {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    } else {
+        12.92 * c
+    }
+}
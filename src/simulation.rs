@@ -0,0 +1,271 @@
+use crate::params::HeightmapParams;
+use crate::save::SavedState;
+use crate::terrain::PaintedTerrain;
+use crate::{Heightmap, River, SimParams, Terrain};
+use std::any::Any;
+use std::path::Path;
+use std::{fs, io};
+
+/// Default fixed time step, one frame at 60 Hz.
+pub static DEFAULT_DT: f32 = 1.0 / 60.0;
+
+/// Fixed-timestep driver for a [`River`] and the terrain it flows over.
+///
+/// Stepping never reads the wall clock and all randomness comes from `seed`,
+/// so two simulations with the same seed, starting river and number of steps
+/// produce bit-identical [`River::segments`].
+#[derive(Clone, Debug)]
+pub struct Simulation {
+    pub river: River,
+    /// Terrain the river flows down.
+    pub heightmap: Box<dyn Terrain>,
+    /// Scaled and offset by [`SimParams::width_scale`] and
+    /// [`SimParams::width_base`] to give the river width.
+    pub widthmap: Box<dyn Terrain>,
+    pub seed: u64,
+    /// Noise seeds `heightmap` and `widthmap` were built from.
+    pub height_seed: u32,
+    pub width_seed: u32,
+    pub dt: f32,
+    pub steps: u64,
+}
+
+impl Simulation {
+    pub fn new(seed: u64, dt: f32, params: SimParams) -> Self {
+        let (height_seed, width_seed) = split_seed(seed);
+        Simulation::with_seeds(seed, height_seed, width_seed, dt, params)
+    }
+
+    /// Like [`Simulation::new`], but with the noise seeds chosen explicitly
+    /// rather than derived from `seed`.
+    pub fn with_seeds(
+        seed: u64,
+        height_seed: u32,
+        width_seed: u32,
+        dt: f32,
+        params: SimParams,
+    ) -> Self {
+        Simulation {
+            river: River {
+                params,
+                forces: params.migration.forces(),
+                ..River::default()
+            },
+            heightmap: Box::new(Heightmap::new(height_seed, &params.heightmap)),
+            widthmap: Box::new(Heightmap::new(width_seed, &params.widthmap)),
+            seed,
+            height_seed,
+            width_seed,
+            dt,
+            steps: 0,
+        }
+    }
+
+    /// Switches to new parameters mid-run, rebuilding the forces if the
+    /// migration model changed and regenerating noise terrain whose
+    /// parameters changed.
+    pub fn set_params(&mut self, params: SimParams) {
+        let old = std::mem::replace(&mut self.river.params, params);
+        if params.migration != old.migration {
+            self.river.forces = params.migration.forces();
+        }
+        if params.heightmap != old.heightmap {
+            regenerate(&mut self.heightmap, self.height_seed, &params.heightmap);
+        }
+        if params.widthmap != old.widthmap {
+            regenerate(&mut self.widthmap, self.width_seed, &params.widthmap);
+        }
+    }
+
+    /// Regenerates the noise terrain from new seeds.
+    pub fn set_seeds(&mut self, height_seed: u32, width_seed: u32) {
+        let params = self.river.params;
+        if height_seed != self.height_seed {
+            self.height_seed = height_seed;
+            regenerate(&mut self.heightmap, height_seed, &params.heightmap);
+        }
+        if width_seed != self.width_seed {
+            self.width_seed = width_seed;
+            regenerate(&mut self.widthmap, width_seed, &params.widthmap);
+        }
+    }
+
+    /// Elapsed simulation time in seconds.
+    pub fn time(&self) -> f64 {
+        self.river.time
+    }
+
+    pub fn step(&mut self) {
+        self.river.recompute();
+        self.river.step(self.dt, &*self.heightmap, &*self.widthmap);
+        self.river.distribute();
+        self.river.tesselate(&*self.widthmap);
+        self.steps += 1;
+    }
+
+    pub fn run(&mut self, steps: u64) {
+        for _ in 0..steps {
+            self.step();
+        }
+    }
+
+    /// Writes the river, terrain seeds and clock to a JSON save file.
+    ///
+    /// Terrain is stored as the noise it was generated from, so a heightmap
+    /// or widthmap replaced by some other [`Terrain`] must be reattached
+    /// after [`Simulation::load`].
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let json = serde_json::to_string(&SavedState::new(self)).map_err(io::Error::other)?;
+        fs::write(path, json)
+    }
+
+    /// Restores a simulation written by [`Simulation::save`]. Stepping it
+    /// continues exactly as the original would have.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let state: SavedState = serde_json::from_str(&fs::read_to_string(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        state.restore()
+    }
+}
+
+/// Decides when to snapshot floodplain history, by sim time so the history
+/// comes out the same however fast the simulation is run or shown.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SnapshotClock {
+    last: Option<f64>,
+}
+
+impl SnapshotClock {
+    /// Whether a snapshot is due at sim time `time`, taking one every `every`
+    /// sim seconds from the first. Counts the snapshot as taken if it is.
+    pub fn due(&mut self, time: f64, every: f32) -> bool {
+        let due = self.last.is_none_or(|at| time - at >= every as f64);
+        if due {
+            self.last = Some(time);
+        }
+        due
+    }
+}
+
+/// Replaces `terrain` with fresh noise, keeping any edits painted over it.
+fn regenerate(terrain: &mut Box<dyn Terrain>, seed: u32, params: &HeightmapParams) {
+    let noise = Box::new(Heightmap::new(seed, params));
+    let any: &mut dyn Any = &mut **terrain;
+    match any.downcast_mut::<PaintedTerrain>() {
+        Some(painted) => painted.base = noise,
+        None => *terrain = noise,
+    }
+}
+
+/// Splits a run seed into the heightmap and widthmap noise seeds.
+pub fn split_seed(seed: u64) -> (u32, u32) {
+    let mixed = splitmix64(seed);
+    (mixed as u32, (mixed >> 32) as u32)
+}
+
+// https://prng.di.unimi.it/splitmix64.c
+fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::save::SAVE_VERSION;
+    use crate::{Node, Preset, apply_preset};
+
+    fn preset_sim(preset: Preset) -> Simulation {
+        let mut sim = Simulation::new(7, DEFAULT_DT, SimParams::default());
+        apply_preset(&mut sim.river, preset);
+        sim
+    }
+
+    /// Every float of every segment, as bits so NaNs compare equal.
+    fn segment_bits(river: &River) -> Vec<u32> {
+        let node_bits = |n: &Node| {
+            [
+                n.loc.x,
+                n.loc.y,
+                n.tangent.x,
+                n.tangent.y,
+                n.bitangent.x,
+                n.bitangent.y,
+                n.curvature,
+                n.upstream_curvature,
+                n.color.x,
+                n.color.y,
+                n.color.z,
+                n.color.w,
+            ]
+            .map(f32::to_bits)
+        };
+        river.segments.iter().flat_map(node_bits).collect()
+    }
+
+    #[test]
+    fn same_seed_and_steps_give_identical_segments() {
+        for preset in Preset::ALL {
+            let mut a = preset_sim(preset);
+            let mut b = preset_sim(preset);
+            // Long enough for both presets to cut off their first oxbow.
+            a.run(400);
+            b.run(400);
+            assert!(!a.river.oxbows.is_empty(), "{}", preset.name());
+            assert_eq!(
+                segment_bits(&a.river),
+                segment_bits(&b.river),
+                "{}",
+                preset.name()
+            );
+        }
+    }
+
+    fn oxbow_bits(river: &River) -> Vec<(u64, Vec<u32>)> {
+        (river.oxbows.iter())
+            .map(|lake| {
+                let locs = lake.nodes.iter().flat_map(|n| n.loc.to_array());
+                (lake.created_at.to_bits(), locs.map(f32::to_bits).collect())
+            })
+            .collect()
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("rivermap-{}-{name}.json", std::process::id()))
+    }
+
+    #[test]
+    fn loaded_save_steps_like_the_original() {
+        let mut sim = preset_sim(Preset::ACROSS);
+        sim.run(300);
+        assert!(!sim.river.oxbows.is_empty());
+        let path = temp_path("round-trip");
+        sim.save(&path).unwrap();
+        let mut loaded = Simulation::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        sim.run(100);
+        loaded.run(100);
+        assert_eq!(loaded.steps, sim.steps);
+        assert_eq!(loaded.time().to_bits(), sim.time().to_bits());
+        assert_eq!(segment_bits(&loaded.river), segment_bits(&sim.river));
+        assert_eq!(oxbow_bits(&loaded.river), oxbow_bits(&sim.river));
+    }
+
+    #[test]
+    fn other_save_versions_are_rejected() {
+        let sim = preset_sim(Preset::CIRCLE);
+        let mut state = SavedState::new(&sim);
+        state.version = SAVE_VERSION + 1;
+        let err = state.clone().restore().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let path = temp_path("other-version");
+        fs::write(&path, serde_json::to_string(&state).unwrap()).unwrap();
+        let err = Simulation::load(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,134 @@
+use glam::{IVec2, Vec2};
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Uniform grid over a set of points, used to find cutoff candidates in
+/// [`River::distribute`](crate::River::distribute) without comparing every
+/// pair of nodes.
+///
+/// Points are bucketed by `floor(loc / cell_size)` so any query with a radius
+/// no larger than `cell_size` only needs to look at the 3×3 block of cells
+/// around it.
+#[derive(Clone, Debug, Default)]
+pub struct SpatialGrid {
+    cell_size: f32,
+    locs: Vec<Vec2>,
+    /// Indices into `locs`, ascending within each cell.
+    cells: HashMap<IVec2, Vec<usize>>,
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f32, locs: impl IntoIterator<Item = Vec2>) -> Self {
+        let mut grid = SpatialGrid {
+            cell_size,
+            locs: Vec::new(),
+            cells: HashMap::new(),
+        };
+        grid.rebuild(locs);
+        grid
+    }
+
+    /// Replaces the indexed points, keeping allocations where possible.
+    pub fn rebuild(&mut self, locs: impl IntoIterator<Item = Vec2>) {
+        self.locs.clear();
+        self.locs.extend(locs);
+        for bucket in self.cells.values_mut() {
+            bucket.clear();
+        }
+        for (ind, &loc) in self.locs.iter().enumerate() {
+            let cell = self.cell(loc);
+            self.cells.entry(cell).or_default().push(ind);
+        }
+    }
+
+    fn cell(&self, loc: Vec2) -> IVec2 {
+        (loc / self.cell_size).floor().as_i32()
+    }
+
+    /// Largest index in `inds` whose point lies strictly within `radius` of
+    /// `at`. `radius` must not exceed the grid's cell size.
+    pub fn farthest_within(&self, at: Vec2, radius: f32, inds: Range<usize>) -> Option<usize> {
+        debug_assert!(radius <= self.cell_size);
+        let center = self.cell(at);
+        let mut best: Option<usize> = None;
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let Some(bucket) = self.cells.get(&(center + IVec2::new(dx, dy))) else {
+                    continue;
+                };
+                let floor = best.map_or(inds.start, |b| b.max(inds.start));
+                let found = bucket
+                    .iter()
+                    .rev()
+                    .skip_while(|&&ind| ind >= inds.end)
+                    .take_while(|&&ind| ind >= floor)
+                    .find(|&&ind| (self.locs[ind] - at).length_squared() < radius * radius);
+                if let Some(&ind) = found {
+                    best = Some(ind);
+                }
+            }
+        }
+        best
+    }
+}
+
+/// Brute force equivalent of [`SpatialGrid::farthest_within`], kept as a
+/// reference for benchmarks.
+pub fn farthest_within_scan(
+    locs: &[Vec2],
+    at: Vec2,
+    radius: f32,
+    inds: Range<usize>,
+) -> Option<usize> {
+    locs.iter()
+        .enumerate()
+        .take(inds.end)
+        .skip(inds.start)
+        .rev()
+        .find_map(|(ind, &loc)| ((loc - at).length_squared() < radius * radius).then_some(ind))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::vec2;
+
+    /// A tightly wound river whose bends keep passing close to each other.
+    fn meander(len: usize, spacing: f32) -> Vec<Vec2> {
+        let mut loc = Vec2::ZERO;
+        (0..len)
+            .map(|i| {
+                let t = i as f32 * 0.05;
+                let heading = 1.9 * t.sin() + 0.4 * (t * 0.17).cos();
+                loc += vec2(heading.cos(), heading.sin()) * spacing;
+                loc
+            })
+            .collect()
+    }
+
+    #[test]
+    fn grid_matches_scan() {
+        let radius = 15.1;
+        let locs = meander(2000, 5.0);
+        let len = locs.len();
+        let grid = SpatialGrid::new(radius, locs.iter().copied());
+        for close_margin in [0, 1, 6] {
+            for (ind, &at) in locs.iter().enumerate() {
+                let queries = [
+                    ind + close_margin..len,
+                    ind + close_margin..ind + len / 2 + 1,
+                    ind..ind,
+                    len..len,
+                    ind + 1..ind,
+                ];
+                for inds in queries {
+                    assert_eq!(
+                        grid.farthest_within(at, radius, inds.clone()),
+                        farthest_within_scan(&locs, at, radius, inds.clone()),
+                        "node {ind}, range {inds:?}"
+                    );
+                }
+            }
+        }
+    }
+}
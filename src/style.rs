@@ -0,0 +1,195 @@
+use serde::{Deserialize, Serialize};
+use std::{fs, io, path::Path};
+
+/// How a finished map looks, independent of the river it shows.
+///
+/// Like [`SimParams`](crate::SimParams), a style file only needs to list
+/// what it changes.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MapStyle {
+    pub paper: PaperStyle,
+    pub history: HistoryStyle,
+}
+
+impl MapStyle {
+    pub const NAMES: [&str; 3] = ["folded", "modern", "dark"];
+
+    /// The built-in looks: an old folded map, a clean modern map and a
+    /// dark-mode display.
+    pub fn named(name: &str) -> Option<Self> {
+        let paper = match name {
+            "folded" => PaperStyle::default(),
+            "modern" => PaperStyle::modern(),
+            "dark" => PaperStyle::dark(),
+            _ => return None,
+        };
+        Some(MapStyle {
+            paper,
+            ..Default::default()
+        })
+    }
+
+    pub fn from_toml(text: &str) -> io::Result<Self> {
+        toml::from_str(text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_toml(&fs::read_to_string(path)?)
+    }
+}
+
+/// Coloring of the floodplain history, the bands the river left behind as it
+/// migrated.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryStyle {
+    /// OKLCH colors, as lightness, chroma and hue in degrees, that history
+    /// fades through from newest to `max_age`. At most
+    /// [`HistoryStyle::MAX_PALETTE_STOPS`] are used.
+    pub palette: Vec<[f32; 3]>,
+    /// Age in snapshots after which history is no longer drawn.
+    pub max_age: f32,
+    /// Fill for each snapshot age, repeating. At most
+    /// [`HistoryStyle::MAX_PATTERNS`] are used.
+    pub patterns: Vec<HatchPattern>,
+    /// Hex and stripe spacing as a fraction of the map size.
+    pub pattern_scale: f32,
+}
+
+impl HistoryStyle {
+    pub const MAX_PALETTE_STOPS: usize = 8;
+    pub const MAX_PATTERNS: usize = 16;
+
+    /// The palette trimmed to what the compositor can hold, never empty.
+    pub fn palette(&self) -> &[[f32; 3]] {
+        match self.palette.len() {
+            0 => &[[0.0, 0.0, 0.0]],
+            n => &self.palette[..n.min(Self::MAX_PALETTE_STOPS)],
+        }
+    }
+
+    /// The patterns trimmed to what the compositor can hold, never empty.
+    pub fn patterns(&self) -> &[HatchPattern] {
+        match self.patterns.len() {
+            0 => &[HatchPattern::Solid],
+            n => &self.patterns[..n.min(Self::MAX_PATTERNS)],
+        }
+    }
+}
+
+impl Default for HistoryStyle {
+    fn default() -> Self {
+        HistoryStyle {
+            palette: vec![
+                [0.7, 0.1135, 48.18],
+                [0.7, 0.1135, 130.41],
+                [0.7, 0.1135, 140.41],
+                [0.7, 0.1135, 239.82],
+            ],
+            max_age: 20.0,
+            patterns: vec![
+                HatchPattern::Solid,
+                HatchPattern::Solid,
+                HatchPattern::HexHoles,
+                HatchPattern::HexDots,
+                HatchPattern::Stripes,
+                HatchPattern::OffsetStripes,
+            ],
+            pattern_scale: 0.01,
+        }
+    }
+}
+
+/// Fill of one band of floodplain history.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(u32)]
+pub enum HatchPattern {
+    Solid = 0,
+    /// Solid with a hex grid of round holes.
+    HexHoles = 1,
+    /// A hex grid of round dots.
+    HexDots = 2,
+    /// Diagonal stripes.
+    Stripes = 3,
+    /// [`HatchPattern::Stripes`] shifted by half a period, filling the gaps.
+    OffsetStripes = 4,
+}
+
+/// The paper the map is printed on, which also shows through the river.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PaperStyle {
+    /// Flat `color` with no grain, folds, stains or vignette.
+    pub plain: bool,
+    /// OKLCH color of clean paper.
+    pub color: [f32; 3],
+    /// OKLCH color the grain, folds, stains and vignette darken toward.
+    pub dark: [f32; 3],
+    /// OKLCH color of bank and oxbow lines, which old bank lines in the
+    /// history are also shaded toward.
+    pub ink: [f32; 3],
+    /// Frequency of the large blotches across the map.
+    pub blotch_scale: f32,
+    pub blotch_strength: f32,
+    /// Frequency of the fine grain across the map.
+    pub grain_scale: f32,
+    pub grain_strength: f32,
+    /// Panels across and down the map between fold creases. Zero for none.
+    pub folds: [f32; 2],
+    /// How quickly a crease fades away from its fold line.
+    pub fold_sharpness: f32,
+    /// Coffee rings scattered over the map, at most
+    /// [`PaperStyle::MAX_STAINS`].
+    pub stains: u32,
+    /// How much the corners darken. Zero for none.
+    pub vignette: f32,
+}
+
+impl PaperStyle {
+    pub const MAX_STAINS: u32 = 8;
+
+    /// Crisp off-white paper with no texture.
+    pub fn modern() -> Self {
+        PaperStyle {
+            plain: true,
+            color: [0.97, 0.005, 90.0],
+            ..Default::default()
+        }
+    }
+
+    /// Smooth dark paper for screens.
+    pub fn dark() -> Self {
+        PaperStyle {
+            color: [0.24, 0.015, 250.0],
+            dark: [0.18, 0.02, 250.0],
+            ink: [0.92, 0.02, 250.0],
+            blotch_strength: 0.2,
+            grain_strength: 0.3,
+            folds: [0.0, 0.0],
+            vignette: 0.5,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for PaperStyle {
+    /// An old, folded paper map.
+    fn default() -> Self {
+        PaperStyle {
+            plain: false,
+            color: [0.8386, 0.052, 84.51],
+            dark: [0.7920, 0.057, 85.00],
+            ink: [0.0, 0.0, 0.0],
+            blotch_scale: 10.0,
+            blotch_strength: 0.5,
+            grain_scale: 100.0,
+            grain_strength: 0.7,
+            folds: [4.0, 3.0],
+            fold_sharpness: 16.81,
+            stains: 0,
+            vignette: 0.0,
+        }
+    }
+}
@@ -0,0 +1,230 @@
+//! Vector export for bringing generated rivers into Inkscape or Illustrator.
+//!
+//! World y points up and SVG y points down, so every y is written negated.
+//! Flipping with a transform instead would hide the layers inside a group.
+
+use crate::shading::gamma_correct;
+use crate::{F_HEIGHT, F_WIDTH, River};
+use glam::{Vec2, Vec4};
+use std::fmt::Write;
+use std::{fs, io, path::Path};
+
+/// How to draw the river fill.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum SvgFill {
+    /// The tessellated triangles, in `fill_color` or the per-node colors.
+    #[default]
+    Triangles,
+    /// A single polygon between the banks, in `fill_color`.
+    Outline,
+}
+
+/// Layout and stroke styles for [`to_svg`].
+#[derive(Copy, Clone, Debug)]
+pub struct SvgStyle {
+    /// World size of the exported area, centered on the origin.
+    pub size: Vec2,
+    pub fill: SvgFill,
+    /// Linear RGBA used for the river and oxbow lakes.
+    pub fill_color: Vec4,
+    /// Color [`SvgFill::Triangles`] with the river's per-node colors
+    /// instead of `fill_color`.
+    pub node_colors: bool,
+    pub bank_width: f32,
+    pub oxbow_border_width: f32,
+    pub marker_radius: f32,
+}
+
+impl Default for SvgStyle {
+    fn default() -> Self {
+        SvgStyle {
+            size: Vec2::new(F_WIDTH, F_HEIGHT),
+            fill: SvgFill::Triangles,
+            fill_color: Vec4::new(1.0, 1.0, 1.0, 1.0),
+            node_colors: false,
+            bank_width: 2.0,
+            oxbow_border_width: 1.0,
+            marker_radius: 4.0,
+        }
+    }
+}
+
+/// Renders the river's last tessellation, its oxbow lakes and its endpoints
+/// as an SVG document with one Inkscape layer per feature.
+pub fn to_svg(river: &River, style: &SvgStyle) -> String {
+    let mut svg = String::new();
+    let (w, h) = (style.size.x, style.size.y);
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape" width="{w}" height="{h}" viewBox="{} {} {w} {h}">"#,
+        -w / 2.0,
+        -h / 2.0,
+    )
+    .unwrap();
+
+    layer(&mut svg, "oxbows", |svg| {
+        for lake in &river.oxbows {
+            let outline = lake.outline();
+            if outline.is_empty() {
+                continue;
+            }
+            writeln!(
+                svg,
+                r#"<path d="{}" fill="{}" stroke="black" stroke-width="{}"/>"#,
+                polygon_data(&outline),
+                hex(style.fill_color),
+                style.oxbow_border_width,
+            )
+            .unwrap();
+        }
+    });
+
+    layer(&mut svg, "fill", |svg| match style.fill {
+        SvgFill::Triangles => {
+            let mesh = &river.river_builder;
+            let vertices = mesh.vertices();
+            // Merge runs of same-colored triangles into one path each.
+            let mut run: Option<(String, String)> = None;
+            for tri in mesh.indices().chunks_exact(3) {
+                let color = if style.node_colors {
+                    hex((vertices[tri[0]].1 + vertices[tri[1]].1 + vertices[tri[2]].1) / 3.0)
+                } else {
+                    hex(style.fill_color)
+                };
+                let mut points: Vec<Vec2> = tri.iter().map(|&i| vertices[i].0.truncate()).collect();
+                // Wind every triangle the same way so overlaps stay filled.
+                if signed_area(&points) < 0.0 {
+                    points.swap(1, 2);
+                }
+                match &mut run {
+                    Some((run_color, data)) if *run_color == color => {
+                        data.push_str(&polygon_data(&points));
+                    }
+                    _ => {
+                        if let Some((run_color, data)) = run.take() {
+                            fill_path(svg, &data, &run_color);
+                        }
+                        run = Some((color, polygon_data(&points)));
+                    }
+                }
+            }
+            if let Some((run_color, data)) = run {
+                fill_path(svg, &data, &run_color);
+            }
+        }
+        SvgFill::Outline => {
+            let [left, right] = river.banks();
+            let data = if river.closed {
+                // Two rings wound opposite ways, so the inner one is cut out.
+                let mut right = right;
+                if signed_area(&left).signum() == signed_area(&right).signum() {
+                    right.reverse();
+                }
+                polygon_data(&left) + &polygon_data(&right)
+            } else {
+                let outline: Vec<Vec2> = left.into_iter().chain(right.into_iter().rev()).collect();
+                polygon_data(&outline)
+            };
+            fill_path(svg, &data, &hex(style.fill_color));
+        }
+    });
+
+    layer(&mut svg, "banks", |svg| {
+        for bank in river.banks() {
+            writeln!(
+                svg,
+                r#"<polyline points="{}" fill="none" stroke="black" stroke-width="{}" stroke-linejoin="round" stroke-linecap="round"/>"#,
+                points_list(&bank),
+                style.bank_width,
+            )
+            .unwrap();
+        }
+    });
+
+    layer(&mut svg, "markers", |svg| {
+        for (name, node, color) in [
+            ("start", river.start, "#2a9d3a"),
+            ("end", river.end, "#c0392b"),
+        ] {
+            writeln!(
+                svg,
+                r#"<circle id="{name}" cx="{:.2}" cy="{:.2}" r="{}" fill="{color}" stroke="black" stroke-width="1"/>"#,
+                node.loc.x, -node.loc.y, style.marker_radius,
+            )
+            .unwrap();
+        }
+    });
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+pub fn save_svg(river: &River, style: &SvgStyle, path: impl AsRef<Path>) -> io::Result<()> {
+    fs::write(path, to_svg(river, style))
+}
+
+fn layer(svg: &mut String, name: &str, contents: impl FnOnce(&mut String)) {
+    writeln!(
+        svg,
+        r#"<g id="{name}" inkscape:groupmode="layer" inkscape:label="{name}">"#
+    )
+    .unwrap();
+    contents(svg);
+    svg.push_str("</g>\n");
+}
+
+fn fill_path(svg: &mut String, data: &str, color: &str) {
+    writeln!(
+        svg,
+        r#"<path d="{data}" fill="{color}" fill-rule="nonzero" stroke="none"/>"#
+    )
+    .unwrap();
+}
+
+/// `x,y` pairs for a `points` attribute, y negated.
+fn points_list(points: &[Vec2]) -> String {
+    let mut out = String::new();
+    for p in points {
+        write!(out, "{:.2},{:.2} ", p.x, -p.y).unwrap();
+    }
+    out.pop();
+    out
+}
+
+/// Path data for a closed polygon, y negated.
+fn polygon_data(points: &[Vec2]) -> String {
+    let mut out = String::new();
+    for (i, p) in points.iter().enumerate() {
+        let cmd = if i == 0 { 'M' } else { 'L' };
+        write!(out, "{cmd}{:.2},{:.2}", p.x, -p.y).unwrap();
+    }
+    if !points.is_empty() {
+        out.push('Z');
+    }
+    out
+}
+
+/// Twice the area of the polygon, positive when wound counterclockwise.
+fn signed_area(points: &[Vec2]) -> f32 {
+    let Some(&last) = points.last() else {
+        return 0.0;
+    };
+    let mut prev = last;
+    let mut area = 0.0;
+    for &p in points {
+        area += prev.perp_dot(p);
+        prev = p;
+    }
+    area
+}
+
+/// Linear RGBA to an sRGB hex color, ignoring alpha.
+fn hex(color: Vec4) -> String {
+    let encode = |c: f32| (gamma_correct(c.clamp(0.0, 1.0)) * 255.0).round() as u8;
+    format!(
+        "#{:02x}{:02x}{:02x}",
+        encode(color.x),
+        encode(color.y),
+        encode(color.z)
+    )
+}
@@ -0,0 +1,351 @@
+use glam::{Vec2, vec2};
+use std::any::Any;
+use std::fmt::Debug;
+use std::{io, path::Path};
+
+/// A height field that rivers flow over, or that sets their width.
+///
+/// `Any` lets a `Box<dyn Terrain>` be downcast back to, say, a
+/// [`PaintedTerrain`] to edit it in place.
+pub trait Terrain: TerrainClone + Any + Debug + Send + Sync {
+    fn height(&self, xy: Vec2) -> f32;
+
+    /// Uphill direction and steepness at `xy`.
+    ///
+    /// Defaults to a central difference over one world unit, override it when
+    /// the surface has an exact derivative.
+    fn gradient(&self, xy: Vec2) -> Vec2 {
+        let dx = self.height(xy + vec2(1.0, 0.0)) - self.height(xy - vec2(1.0, 0.0));
+        let dy = self.height(xy + vec2(0.0, 1.0)) - self.height(xy - vec2(0.0, 1.0));
+        vec2(dx, dy) * 0.5
+    }
+}
+
+/// Lets `Box<dyn Terrain>` be cloned along with its simulation.
+pub trait TerrainClone {
+    fn clone_box(&self) -> Box<dyn Terrain>;
+}
+
+impl<T: Terrain + Clone + 'static> TerrainClone for T {
+    fn clone_box(&self) -> Box<dyn Terrain> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Terrain> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// A grayscale image stretched over a rectangle centered on the origin.
+///
+/// Black maps to `low` and white to `high`, with bilinear filtering between
+/// pixels. Points off the image read as `outside`.
+#[derive(Clone, Debug)]
+pub struct ImageTerrain {
+    pixels: Vec<f32>,
+    width: u32,
+    height: u32,
+    /// World size of the whole image.
+    pub size: Vec2,
+    pub low: f32,
+    pub high: f32,
+    pub outside: f32,
+}
+
+impl ImageTerrain {
+    pub fn new(image: &image::GrayImage, size: Vec2) -> Self {
+        ImageTerrain {
+            pixels: image.pixels().map(|p| p.0[0] as f32 / 255.0).collect(),
+            width: image.width(),
+            height: image.height(),
+            size,
+            low: -1.0,
+            high: 1.0,
+            outside: 1.0,
+        }
+    }
+
+    /// Loads any image format `image` understands, converting it to grayscale.
+    pub fn load(path: impl AsRef<Path>, size: Vec2) -> io::Result<Self> {
+        let image = image::open(path).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(Self::new(&image.to_luma8(), size))
+    }
+
+    fn pixel(&self, x: u32, y: u32) -> f32 {
+        let (x, y) = (x.min(self.width - 1), y.min(self.height - 1));
+        self.pixels[(y * self.width + x) as usize]
+    }
+}
+
+impl Terrain for ImageTerrain {
+    fn height(&self, xy: Vec2) -> f32 {
+        // Image rows run top to bottom while world y points up.
+        let uv = vec2(0.5 + xy.x / self.size.x, 0.5 - xy.y / self.size.y);
+        if self.width == 0 || self.height == 0 || uv.min_element() < 0.0 || uv.max_element() > 1.0 {
+            return self.outside;
+        }
+        let texel = uv * vec2(self.width as f32, self.height as f32) - 0.5;
+        let corner = texel.floor().max(Vec2::ZERO);
+        let t = (texel - corner).clamp(Vec2::ZERO, Vec2::ONE);
+        let (x, y) = (corner.x as u32, corner.y as u32);
+        let top = self.pixel(x, y) * (1.0 - t.x) + self.pixel(x + 1, y) * t.x;
+        let bottom = self.pixel(x, y + 1) * (1.0 - t.x) + self.pixel(x + 1, y + 1) * t.x;
+        let value = top * (1.0 - t.y) + bottom * t.y;
+        self.low + (self.high - self.low) * value
+    }
+}
+
+/// A flat surface rising by `slope` per world unit.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TiltedPlane {
+    pub slope: Vec2,
+    pub offset: f32,
+}
+
+impl Terrain for TiltedPlane {
+    fn height(&self, xy: Vec2) -> f32 {
+        self.slope.dot(xy) + self.offset
+    }
+
+    fn gradient(&self, _: Vec2) -> Vec2 {
+        self.slope
+    }
+}
+
+/// A parabolic trough through `center` running along `direction`, optionally
+/// tilted along its axis.
+#[derive(Copy, Clone, Debug)]
+pub struct Valley {
+    pub center: Vec2,
+    /// Unit vector along the valley floor.
+    pub direction: Vec2,
+    /// Distance from the floor at which the walls have risen by `depth`.
+    pub half_width: f32,
+    pub depth: f32,
+    /// Rise per world unit along `direction`.
+    pub fall: f32,
+}
+
+impl Terrain for Valley {
+    fn height(&self, xy: Vec2) -> f32 {
+        let rel = xy - self.center;
+        let across = rel.dot(self.direction.perp()) / self.half_width;
+        self.depth * across * across + self.fall * rel.dot(self.direction)
+    }
+
+    fn gradient(&self, xy: Vec2) -> Vec2 {
+        let normal = self.direction.perp();
+        let across = (xy - self.center).dot(normal);
+        normal * (2.0 * self.depth * across / (self.half_width * self.half_width))
+            + self.direction * self.fall
+    }
+}
+
+/// A paraboloid that is lowest at `center` and has risen by `depth` at `radius`.
+#[derive(Copy, Clone, Debug)]
+pub struct Bowl {
+    pub center: Vec2,
+    pub radius: f32,
+    pub depth: f32,
+}
+
+impl Terrain for Bowl {
+    fn height(&self, xy: Vec2) -> f32 {
+        self.depth * (xy - self.center).length_squared() / (self.radius * self.radius)
+    }
+
+    fn gradient(&self, xy: Vec2) -> Vec2 {
+        (xy - self.center) * (2.0 * self.depth / (self.radius * self.radius))
+    }
+}
+
+/// Hand-painted height offsets layered over another terrain.
+///
+/// The offsets live on a grid of `cell_size` squares covering a `size`
+/// rectangle centered on the origin, and are interpolated bilinearly, so an
+/// edit changes [`Terrain::gradient`] as soon as it is painted. Points off
+/// the grid read as the base terrain.
+#[derive(Clone, Debug)]
+pub struct PaintedTerrain {
+    pub base: Box<dyn Terrain>,
+    offsets: Vec<f32>,
+    columns: usize,
+    rows: usize,
+    cell_size: f32,
+    origin: Vec2,
+}
+
+/// How [`PaintedTerrain::paint`] changes the terrain under the brush.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Brush {
+    Raise,
+    Lower,
+    /// Pulls each point toward the average of its neighbors.
+    Smooth,
+    /// Pulls each point toward the given height.
+    Flatten(f32),
+}
+
+impl PaintedTerrain {
+    pub fn new(base: Box<dyn Terrain>, size: Vec2, cell_size: f32) -> Self {
+        let columns = (size.x / cell_size).ceil() as usize + 1;
+        let rows = (size.y / cell_size).ceil() as usize + 1;
+        PaintedTerrain {
+            base,
+            offsets: vec![0.0; columns * rows],
+            columns,
+            rows,
+            cell_size,
+            origin: -size / 2.0,
+        }
+    }
+
+    /// Height added to the base terrain at `xy`.
+    pub fn offset(&self, xy: Vec2) -> f32 {
+        let grid = (xy - self.origin) / self.cell_size;
+        let last = vec2((self.columns - 1) as f32, (self.rows - 1) as f32);
+        if grid.min_element() < 0.0 || grid.x > last.x || grid.y > last.y {
+            return 0.0;
+        }
+        let corner = grid.floor().min(last - 1.0).max(Vec2::ZERO);
+        let t = grid - corner;
+        let (x, y) = (corner.x as usize, corner.y as usize);
+        let at = |x: usize, y: usize| self.offsets[y * self.columns + x];
+        let (x1, y1) = ((x + 1).min(self.columns - 1), (y + 1).min(self.rows - 1));
+        let bottom = at(x, y) * (1.0 - t.x) + at(x1, y) * t.x;
+        let top = at(x, y1) * (1.0 - t.x) + at(x1, y1) * t.x;
+        bottom * (1.0 - t.y) + top * t.y
+    }
+
+    /// Applies `brush` to the grid points within `radius` of `center`, with
+    /// full `strength` in the middle falling off smoothly to nothing at the
+    /// rim. Raising and lowering move the terrain by `strength` height units,
+    /// smoothing and flattening move it that fraction of the way to their
+    /// target.
+    pub fn paint(&mut self, brush: Brush, center: Vec2, radius: f32, strength: f32) {
+        let lo = ((center - radius - self.origin) / self.cell_size)
+            .ceil()
+            .max(Vec2::ZERO);
+        let last = vec2((self.columns - 1) as f32, (self.rows - 1) as f32);
+        let hi = ((center + radius - self.origin) / self.cell_size)
+            .floor()
+            .min(last);
+        if hi.x < lo.x || hi.y < lo.y {
+            return;
+        }
+        let mut changes = Vec::new();
+        for y in lo.y as usize..=hi.y as usize {
+            for x in lo.x as usize..=hi.x as usize {
+                let xy = self.origin + vec2(x as f32, y as f32) * self.cell_size;
+                let d = xy.distance(center) / radius;
+                if d >= 1.0 {
+                    continue;
+                }
+                let weight = strength * (1.0 - d * d) * (1.0 - d * d);
+                let change = match brush {
+                    Brush::Raise => weight,
+                    Brush::Lower => -weight,
+                    Brush::Smooth => {
+                        let neighbors = [
+                            vec2(1.0, 0.0),
+                            vec2(-1.0, 0.0),
+                            vec2(0.0, 1.0),
+                            vec2(0.0, -1.0),
+                        ]
+                        .map(|dir| self.height(xy + dir * self.cell_size));
+                        let average = neighbors.iter().sum::<f32>() / 4.0;
+                        (average - self.height(xy)) * weight.min(1.0)
+                    }
+                    Brush::Flatten(target) => (target - self.height(xy)) * weight.min(1.0),
+                };
+                changes.push((y * self.columns + x, change));
+            }
+        }
+        // Applied afterwards so smoothing reads the terrain from before this dab.
+        for (i, change) in changes {
+            self.offsets[i] += change;
+        }
+    }
+
+    /// Removes every edit.
+    pub fn clear(&mut self) {
+        self.offsets.fill(0.0);
+    }
+}
+
+impl Terrain for PaintedTerrain {
+    fn height(&self, xy: Vec2) -> f32 {
+        self.base.height(xy) + self.offset(xy)
+    }
+
+    fn gradient(&self, xy: Vec2) -> Vec2 {
+        let dx = self.offset(xy + vec2(1.0, 0.0)) - self.offset(xy - vec2(1.0, 0.0));
+        let dy = self.offset(xy + vec2(0.0, 1.0)) - self.offset(xy - vec2(0.0, 1.0));
+        self.base.gradient(xy) + vec2(dx, dy) * 0.5
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hides a terrain's own gradient so the default finite difference runs.
+    #[derive(Clone, Debug)]
+    struct HeightOnly<T>(T);
+
+    impl<T: Terrain + Clone> Terrain for HeightOnly<T> {
+        fn height(&self, xy: Vec2) -> f32 {
+            self.0.height(xy)
+        }
+    }
+
+    fn assert_gradients_match(terrain: impl Terrain + Clone) {
+        let numeric = HeightOnly(terrain.clone());
+        for y in (-40..=40).step_by(10) {
+            for x in (-40..=40).step_by(10) {
+                let xy = vec2(x as f32, y as f32);
+                let (exact, approx) = (terrain.gradient(xy), numeric.gradient(xy));
+                assert!(
+                    exact.abs_diff_eq(approx, 1e-3),
+                    "{terrain:?} at {xy}: {exact} vs {approx}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn analytic_gradients_match_finite_differences() {
+        assert_gradients_match(Valley {
+            center: vec2(5.0, -3.0),
+            direction: vec2(0.6, 0.8),
+            half_width: 30.0,
+            depth: 4.0,
+            fall: -0.05,
+        });
+        assert_gradients_match(Bowl {
+            center: vec2(-7.0, 2.0),
+            radius: 50.0,
+            depth: 6.0,
+        });
+    }
+
+    #[test]
+    fn image_is_filtered_between_pixels_and_flipped() {
+        // Top row black then white, bottom row white then 20% gray.
+        let image = image::GrayImage::from_raw(2, 2, vec![0, 255, 255, 51]).unwrap();
+        let terrain = ImageTerrain {
+            outside: 5.0,
+            ..ImageTerrain::new(&image, vec2(2.0, 2.0))
+        };
+        let at = |x: f32, y: f32| terrain.height(vec2(x, y));
+        assert!((at(-1.0, 1.0) - -1.0).abs() < 1e-5);
+        assert!((at(1.0, 1.0) - 1.0).abs() < 1e-5);
+        assert!((at(-1.0, -1.0) - 1.0).abs() < 1e-5);
+        assert!((at(1.0, -1.0) - -0.6).abs() < 1e-5);
+        assert!((at(0.0, 0.0) - 0.1).abs() < 1e-5);
+        assert_eq!(at(1.5, 0.0), 5.0);
+        assert_eq!(at(0.0, -1.5), 5.0);
+    }
+}
@@ -0,0 +1,433 @@
+use nannou::prelude::*;
+use nannou::winit::event::WindowEvent;
+use nannou_egui::Egui;
+use rivermap::save::{SavedNode, SavedOxbow};
+use rivermap::shading::oklch_to_lin;
+use rivermap::simulation::{Simulation, SnapshotClock};
+use rivermap::style::MapStyle;
+use rivermap::terrain::{Brush, PaintedTerrain};
+use rivermap::{F_HEIGHT, F_WIDTH, HEIGHT, Node, OxbowLake, River, WIDTH};
+use std::any::Any;
+use std::collections::VecDeque;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::compositor::Compositor;
+use crate::debug::{self, DebugLayers};
+use crate::history::History;
+use crate::panel::{self, ViewSettings};
+use crate::render::Render;
+
+/// What the window starts with. nannou builds the model from a plain `fn`,
+/// so this is handed over through a static.
+#[derive(Clone, Debug)]
+pub struct ViewOptions {
+    pub sim: Simulation,
+    pub style: MapStyle,
+}
+
+static OPTIONS: OnceLock<ViewOptions> = OnceLock::new();
+
+/// Grid spacing of the terrain edits, in world units.
+static PAINT_CELL_SIZE: f32 = 4.0;
+static MIN_BRUSH_RADIUS: f32 = 5.0;
+static MAX_BRUSH_RADIUS: f32 = 300.0;
+/// Height the raise and lower brushes add per second at their center.
+static RAISE_PER_SECOND: f32 = 0.5;
+/// How much of the way the smooth and flatten brushes go per second at their center.
+static BLEND_PER_SECOND: f32 = 4.0;
+
+/// How many past steps `Left` can undo.
+static REWIND_STEPS: usize = 600;
+static MIN_SPEED: f32 = 1.0 / 16.0;
+static MAX_SPEED: f32 = 16.0;
+
+/// What dragging with the left mouse button does.
+///
+/// `D` sketches a new river course, `R`, `L`, `S` and `F` raise, lower,
+/// smooth and flatten the terrain, `[` and `]` resize the brush and `C`
+/// clears the terrain edits.
+///
+/// Independently of the tool, `Space` pauses, `Right` and `Left` step forward
+/// and back one step, `Up` and `Down` double and halve the speed, and `P`
+/// shows and hides the parameter panel and `G` the debug overlay.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Tool {
+    Sketch,
+    Paint(Brush),
+}
+
+pub fn run(options: ViewOptions) {
+    OPTIONS.set(options).unwrap();
+    nannou::app(model).update(update).run();
+}
+
+fn model(app: &App) -> Model {
+    app.set_exit_on_escape(true);
+    app.new_window()
+        .size(WIDTH, HEIGHT)
+        .msaa_samples(4)
+        .view(view)
+        .resized(resized)
+        .key_pressed(key_pressed)
+        .mouse_pressed(mouse_pressed)
+        .mouse_moved(mouse_moved)
+        .mouse_released(mouse_released)
+        .raw_event(raw_window_event)
+        .build()
+        .unwrap();
+    let model = Model::new(app, OPTIONS.get().unwrap().clone());
+    model.update_title(app);
+    model
+}
+
+/// Rebuilds the window-sized targets. The history covers the map rather
+/// than the window, so it is kept and just projected onto the new size.
+fn resized(app: &App, model: &mut Model, _size: Vec2) {
+    // Moving to a screen with a different DPI also resizes the window.
+    model.river_history.rescale(app);
+    model.border = Render::new(app);
+    model.fill = Render::new(app);
+    let textures = [
+        &model.river_history.texture,
+        &model.border.texture,
+        &model.fill.texture,
+    ];
+    model.compositor = Compositor::new(app, &textures, &model.view_settings.style);
+}
+
+fn raw_window_event(_app: &App, model: &mut Model, event: &WindowEvent) {
+    model.egui.handle_raw_event(event);
+}
+
+fn key_pressed(app: &App, model: &mut Model, key: Key) {
+    if model.egui.ctx().wants_keyboard_input() {
+        return;
+    }
+    let tool = match key {
+        Key::Space => {
+            model.paused = !model.paused;
+            return model.update_title(app);
+        }
+        Key::Right => {
+            model.paused = true;
+            model.step(app);
+            return model.update_title(app);
+        }
+        Key::Left => {
+            model.paused = true;
+            model.step_back();
+            return model.update_title(app);
+        }
+        Key::Up => {
+            model.speed = (model.speed * 2.0).min(MAX_SPEED);
+            return model.update_title(app);
+        }
+        Key::Down => {
+            model.speed = (model.speed / 2.0).max(MIN_SPEED);
+            return model.update_title(app);
+        }
+        Key::D => Tool::Sketch,
+        Key::R => Tool::Paint(Brush::Raise),
+        Key::L => Tool::Paint(Brush::Lower),
+        Key::S => Tool::Paint(Brush::Smooth),
+        Key::F => Tool::Paint(Brush::Flatten(0.0)),
+        Key::LBracket => {
+            model.brush_radius = (model.brush_radius / 1.25).max(MIN_BRUSH_RADIUS);
+            return;
+        }
+        Key::RBracket => {
+            model.brush_radius = (model.brush_radius * 1.25).min(MAX_BRUSH_RADIUS);
+            return;
+        }
+        Key::G => {
+            model.view_settings.debug.enabled = !model.view_settings.debug.enabled;
+            return;
+        }
+        Key::P => {
+            model.show_panel = !model.show_panel;
+            return;
+        }
+        Key::C => {
+            if let Some(terrain) = model.painted_terrain() {
+                terrain.clear();
+            }
+            return;
+        }
+        _ => return,
+    };
+    // Pressing a tool's key again puts it down.
+    model.tool = (model.tool != Some(tool)).then_some(tool);
+    model.stroke = None;
+    model.painting = None;
+}
+
+fn mouse_pressed(app: &App, model: &mut Model, button: MouseButton) {
+    if model.egui.ctx().wants_pointer_input() {
+        return;
+    }
+    if button != MouseButton::Left {
+        return;
+    }
+    let pos = app.mouse.position();
+    match model.tool {
+        Some(Tool::Sketch) => model.stroke = Some(vec![pos]),
+        // Flatten to whatever height the stroke started on.
+        Some(Tool::Paint(Brush::Flatten(_))) => {
+            model.painting = Some(Brush::Flatten(model.sim.heightmap.height(pos)))
+        }
+        Some(Tool::Paint(brush)) => model.painting = Some(brush),
+        None => {}
+    }
+}
+
+fn mouse_moved(_app: &App, model: &mut Model, pos: Point2) {
+    if let Some(stroke) = &mut model.stroke
+        && stroke.last().is_none_or(|last| last.distance(pos) >= 1.0)
+    {
+        stroke.push(pos);
+    }
+}
+
+fn mouse_released(_app: &App, model: &mut Model, button: MouseButton) {
+    if button != MouseButton::Left {
+        return;
+    }
+    model.painting = None;
+    let Some(stroke) = model.stroke.take() else {
+        return;
+    };
+    let river = &mut model.sim.river;
+    let length: f32 = stroke.windows(2).map(|w| w[0].distance(w[1])).sum();
+    // A click or a twitch isn't a river.
+    if length >= river.params.min_distance * 2.0 {
+        let color = river.start.color;
+        river.set_centerline(&stroke, color);
+    }
+}
+
+fn update(app: &App, model: &mut Model, update: Update) {
+    model.egui.set_elapsed_time(update.since_start);
+    let ctx = model.egui.begin_frame();
+    if model.show_panel {
+        let style = model.view_settings.style.clone();
+        panel::show(&ctx, &mut model.sim, &mut model.view_settings);
+        if model.view_settings.style != style {
+            model.compositor.set_style(app, &model.view_settings.style);
+        }
+    }
+    ctx.end();
+
+    if let Some(brush) = model.painting {
+        let rate = match brush {
+            Brush::Raise | Brush::Lower => RAISE_PER_SECOND,
+            Brush::Smooth | Brush::Flatten(_) => BLEND_PER_SECOND,
+        };
+        let strength = rate * update.since_last.as_secs_f32();
+        let (pos, radius) = (app.mouse.position(), model.brush_radius);
+        if let Some(terrain) = model.painted_terrain() {
+            terrain.paint(brush, pos, radius, strength);
+        }
+    }
+    // Hold the river still while a new course is being sketched.
+    if model.paused || model.stroke.is_some() {
+        model.step_debt = 0.0;
+        return;
+    }
+    model.step_debt += update
+        .since_last
+        .min(Duration::from_millis(200))
+        .as_secs_f32()
+        * model.speed;
+    while model.step_debt >= model.sim.dt {
+        model.step(app);
+        model.step_debt -= model.sim.dt;
+    }
+}
+
+fn view(app: &App, model: &Model, mut frame: Frame) {
+    // Begin drawing
+    let draw = app.draw();
+    model.draw(app, &mut frame);
+    debug::draw_overlay(&draw, &model.sim, &model.view_settings.debug);
+    if let Some(Tool::Paint(_)) = model.tool {
+        draw.ellipse()
+            .xy(app.mouse.position())
+            .radius(model.brush_radius)
+            .no_fill()
+            .stroke(BLACK)
+            .stroke_weight(1.0);
+    }
+    if let Some(stroke) = &model.stroke {
+        draw.polyline()
+            .weight(2.0)
+            .points(stroke.iter().copied())
+            .color(BLACK);
+    }
+    // Write the result of our drawing to the window's frame.
+    draw.to_frame(app, &frame).unwrap();
+    model.egui.draw_to_frame(&frame).unwrap();
+}
+
+struct Model {
+    sim: Simulation,
+    step_debt: f32,
+    tool: Option<Tool>,
+    /// The course being sketched, in window coordinates.
+    stroke: Option<Vec<Vec2>>,
+    /// The brush being dragged over the terrain.
+    painting: Option<Brush>,
+    brush_radius: f32,
+    paused: bool,
+    /// Sim seconds per wall-clock second.
+    speed: f32,
+    /// The river before each of the most recent steps, oldest first.
+    rewind: VecDeque<Rewind>,
+    river_history: History,
+    border: Render,
+    fill: Render,
+    snapshots: SnapshotClock,
+    compositor: Compositor,
+    /// Toggled with `P`.
+    show_panel: bool,
+    view_settings: ViewSettings,
+    egui: Egui,
+}
+
+/// The shape, clock and oxbow lakes of the river before a step.
+///
+/// Parameters and forces are left out so stepping back keeps any edits made
+/// in the panel since, and meshes are left out as they are quick to rebuild.
+struct Rewind {
+    time: f64,
+    closed: bool,
+    start: SavedNode,
+    segments: Vec<SavedNode>,
+    end: SavedNode,
+    oxbows: Vec<SavedOxbow>,
+}
+
+impl Rewind {
+    fn new(river: &River) -> Self {
+        Rewind {
+            time: river.time,
+            closed: river.closed,
+            start: (&river.start).into(),
+            segments: river.segments.iter().map(SavedNode::from).collect(),
+            end: (&river.end).into(),
+            oxbows: river.oxbows.iter().map(SavedOxbow::from).collect(),
+        }
+    }
+
+    /// Puts `river` back as it was, without its meshes.
+    fn restore(self, river: &mut River) {
+        river.time = self.time;
+        river.closed = self.closed;
+        river.start = self.start.into();
+        river.segments = self.segments.into_iter().map(Node::from).collect();
+        river.end = self.end.into();
+        river.oxbows = self.oxbows.into_iter().map(OxbowLake::from).collect();
+        river.recompute();
+    }
+}
+
+impl Model {
+    pub fn new(app: &App, options: ViewOptions) -> Self {
+        let river_history = History::new(app);
+        let border = Render::new(app);
+        let fill = Render::new(app);
+        let textures = [&river_history.texture, &border.texture, &fill.texture];
+        let compositor = Compositor::new(app, &textures, &options.style);
+
+        let mut sim = options.sim;
+        sim.heightmap = Box::new(PaintedTerrain::new(
+            sim.heightmap,
+            vec2(F_WIDTH, F_HEIGHT),
+            PAINT_CELL_SIZE,
+        ));
+
+        Model {
+            sim,
+            step_debt: 0.0,
+            tool: None,
+            stroke: None,
+            painting: None,
+            brush_radius: 40.0,
+            paused: false,
+            speed: 1.0,
+            rewind: VecDeque::with_capacity(REWIND_STEPS),
+            snapshots: SnapshotClock::default(),
+            show_panel: true,
+            view_settings: ViewSettings {
+                history_every: 0.5,
+                debug: DebugLayers::default(),
+                style: options.style,
+            },
+            egui: Egui::from_window(&app.main_window()),
+            river_history,
+            border,
+            fill,
+            compositor,
+        }
+    }
+
+    /// Steps the simulation, stamping the river into the history if a
+    /// snapshot is due.
+    fn step(&mut self, app: &App) {
+        if self.rewind.len() == REWIND_STEPS {
+            self.rewind.pop_front();
+        }
+        self.rewind.push_back(Rewind::new(&self.sim.river));
+        self.sim.step();
+        let time = self.sim.time();
+        if self.snapshots.due(time, self.view_settings.history_every) {
+            self.river_history.stamp(app, &self.sim.river, time as f32);
+        }
+    }
+
+    /// Undoes the last step, if it is still in the rewind buffer.
+    fn step_back(&mut self) {
+        if let Some(rewind) = self.rewind.pop_back() {
+            rewind.restore(&mut self.sim.river);
+            self.sim.river.tesselate(&*self.sim.widthmap);
+            self.sim.steps -= 1;
+        }
+    }
+
+    fn update_title(&self, app: &App) {
+        let state = if self.paused { "paused" } else { "running" };
+        app.main_window().set_title(&format!(
+            "rivermap - {state} at {}x, step {}",
+            self.speed, self.sim.steps
+        ));
+    }
+
+    /// The editable layer [`Model::new`] put over the heightmap, unless the
+    /// heightmap has since been replaced.
+    fn painted_terrain(&mut self) -> Option<&mut PaintedTerrain> {
+        let heightmap: &mut dyn Any = &mut *self.sim.heightmap;
+        heightmap.downcast_mut()
+    }
+
+    pub fn draw(&self, app: &App, frame: &mut Frame) {
+        let time = self.sim.time() as f32;
+        self.compositor
+            .set_time(app, time, self.view_settings.history_every);
+
+        self.fill.render_frame(app, frame, |_, draw| {
+            draw.background().rgba(0.0, 0.0, 0.0, 0.0);
+            self.sim.river.draw_oxbow_fill(draw);
+            self.sim.river.draw_fill(draw)
+        });
+
+        let ink = oklch_to_lin(Vec3::from(self.view_settings.style.paper.ink));
+        let ink = lin_srgba(ink.x, ink.y, ink.z, 1.0);
+        self.border.render_frame(app, frame, |_, draw| {
+            draw.background().rgba(0.0, 0.0, 0.0, 0.0);
+            self.sim.river.draw_oxbow_border(draw, ink);
+            self.sim.river.draw_border(draw, ink)
+        });
+
+        self.compositor.draw(frame);
+    }
+}